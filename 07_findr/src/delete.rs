@@ -0,0 +1,53 @@
+use crate::entry::Entry;
+use std::{fs, path::Path};
+
+/// Removes the entries for which a `-delete` action is evaluated
+#[derive(Debug, Clone)]
+pub struct Delete {
+    /// Only list the entries instead of removing them
+    dry_run: bool,
+    failed: bool,
+}
+
+impl Delete {
+    pub fn new(dry_run: bool) -> Self {
+        Delete {
+            dry_run,
+            failed: false,
+        }
+    }
+
+    pub fn is_dry_run(&self) -> bool {
+        self.dry_run
+    }
+
+    /// Remove the entry, returning whether it succeeded
+    ///
+    /// A directory can only be removed once its contents are gone, so the
+    /// walk must evaluate directories after their contents.
+    pub fn run(&mut self, entry: &Entry) -> bool {
+        let path = entry.path();
+        // Like find, leave the current directory alone
+        if path == Path::new(".") {
+            return true;
+        }
+        let res = if entry.file_type().is_dir() && !path.is_symlink() {
+            fs::remove_dir(path)
+        } else {
+            fs::remove_file(path)
+        };
+        match res {
+            Ok(()) => true,
+            Err(e) => {
+                eprintln!("{}: {}", path.display(), e);
+                self.failed = true;
+                false
+            }
+        }
+    }
+
+    /// Whether every entry was removed
+    pub fn finish(&self) -> bool {
+        !self.failed
+    }
+}
@@ -0,0 +1,130 @@
+use std::{
+    cell::{Cell, OnceCell},
+    ffi::OsStr,
+    fs::{self, FileType, Metadata},
+    path::Path,
+};
+use walkdir::DirEntry;
+
+/// A walked entry whose metadata is read at most once, however many tests
+/// need it
+#[derive(Debug)]
+pub struct Entry<'a> {
+    path: &'a Path,
+    depth: usize,
+    /// The type of the entry, or of its target if it is a followed link
+    file_type: FileType,
+    /// Whether the entry is a symbolic link that was followed
+    followed: bool,
+    metadata: OnceCell<Option<Metadata>>,
+    pruned: Cell<bool>,
+}
+
+impl<'a> Entry<'a> {
+    /// The entry for `dent`, which is followed if it is a symbolic link
+    /// given as a search path and `follow_path` is set
+    pub fn new(dent: &'a DirEntry, follow_path: bool) -> Self {
+        // walkdir descends into such links but still reports them as links
+        if follow_path && dent.depth() == 0 && dent.path_is_symlink() {
+            if let Ok(metadata) = fs::metadata(dent.path()) {
+                let entry = Entry::with_type(dent.path(), 0, metadata.file_type(), true);
+                let _ = entry.metadata.set(Some(metadata));
+                return entry;
+            }
+        }
+        let followed = dent.path_is_symlink() && !dent.file_type().is_symlink();
+        Entry::with_type(dent.path(), dent.depth(), dent.file_type(), followed)
+    }
+
+    /// An entry found at `depth` whose type is already known, like a broken
+    /// link that could not be followed
+    pub fn with_type(path: &'a Path, depth: usize, file_type: FileType, followed: bool) -> Self {
+        Entry {
+            path,
+            depth,
+            file_type,
+            followed,
+            metadata: OnceCell::new(),
+            pruned: Cell::new(false),
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        self.path
+    }
+
+    /// The last component of the path, or the whole path if it has none,
+    /// like `.`
+    pub fn file_name(&self) -> &OsStr {
+        self.path.file_name().unwrap_or(self.path.as_os_str())
+    }
+
+    pub fn file_type(&self) -> FileType {
+        self.file_type
+    }
+
+    /// The type of the target of the entry if it is a link that was not
+    /// followed, or of the link itself if it was, or else of the entry;
+    /// a broken link is its own target
+    pub fn xtype(&self) -> FileType {
+        let metadata = if self.followed {
+            fs::symlink_metadata(self.path)
+        } else if self.file_type.is_symlink() {
+            fs::metadata(self.path)
+        } else {
+            return self.file_type;
+        };
+        metadata.map_or(self.file_type, |metadata| metadata.file_type())
+    }
+
+    /// The depth of the entry below the search path it was found in
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+
+    /// The entry's metadata, or its target's if it is a followed link, or
+    /// `None` if it could not be read, in which case the error is reported
+    /// the first time only
+    pub fn metadata(&self) -> Option<&Metadata> {
+        self.metadata
+            .get_or_init(|| {
+                let metadata = if self.followed {
+                    fs::metadata(self.path)
+                } else {
+                    fs::symlink_metadata(self.path)
+                };
+                match metadata {
+                    Ok(metadata) => Some(metadata),
+                    Err(e) => {
+                        eprintln!("{}: {}", self.path.display(), e);
+                        None
+                    }
+                }
+            })
+            .as_ref()
+    }
+
+    /// Keep the walk from descending into the entry
+    pub fn prune(&self) {
+        self.pruned.set(true);
+    }
+
+    pub fn is_pruned(&self) -> bool {
+        self.pruned.get()
+    }
+
+    /// Whether the entry is an empty regular file or an empty directory
+    pub fn is_empty(&self) -> bool {
+        match self.metadata() {
+            Some(metadata) if metadata.is_file() => metadata.len() == 0,
+            Some(metadata) if metadata.is_dir() => match fs::read_dir(self.path()) {
+                Ok(mut entries) => entries.next().is_none(),
+                Err(e) => {
+                    eprintln!("{}: {}", self.path().display(), e);
+                    false
+                }
+            },
+            _ => false,
+        }
+    }
+}
@@ -0,0 +1,198 @@
+use crate::MyResult;
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use ignore::Match;
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+/// Decides which entries are left out of the walk of a search path, along
+/// with everything below them
+pub struct Exclusions {
+    root: PathBuf,
+    /// The absolute version of `root`, as ignore files are matched against
+    /// absolute paths
+    abs_root: PathBuf,
+    /// The `--exclude` patterns, which take precedence over ignore files
+    exclude: Gitignore,
+    ignore_files: Option<IgnoreFiles>,
+}
+
+impl Exclusions {
+    /// Build the exclusions for a walk from `root`, with ignore files if
+    /// `gitignore` is set
+    pub fn new(root: &str, patterns: &[String], gitignore: bool) -> MyResult<Self> {
+        let root = PathBuf::from(root);
+        let abs_root = fs::canonicalize(&root)
+            .or_else(|_| std::path::absolute(&root))
+            .unwrap_or_else(|_| root.clone());
+        let mut builder = GitignoreBuilder::new(&abs_root);
+        for pattern in patterns {
+            builder
+                .add_line(None, pattern)
+                .map_err(|e| format!("invalid value '{}' for '--exclude': {}", pattern, e))?;
+        }
+        Ok(Exclusions {
+            exclude: builder.build()?,
+            ignore_files: gitignore.then(|| IgnoreFiles::new(&abs_root)),
+            root,
+            abs_root,
+        })
+    }
+
+    /// Whether the entry at `path` found at `depth` is excluded, which is
+    /// never the case of the search path itself
+    ///
+    /// `levels` must hold the ignore files of the directories above the
+    /// entry, which are those left by the entry's parent directory or by any
+    /// entry walked after it. They become those of the entry if it is a
+    /// directory that is not excluded.
+    pub fn is_excluded(
+        &self,
+        path: &Path,
+        is_dir: bool,
+        depth: usize,
+        levels: &mut Levels,
+    ) -> bool {
+        levels.0.truncate(depth);
+        if depth == 0 {
+            if let Some(ignore_files) = &self.ignore_files {
+                levels.0.push(ignore_files.level(&self.abs_root, &levels.0));
+            }
+            return false;
+        }
+        let path = match path.strip_prefix(&self.root) {
+            Ok(rel) => self.abs_root.join(rel),
+            Err(_) => path.to_path_buf(),
+        };
+        let excluded = match self.exclude.matched(&path, is_dir) {
+            Match::Ignore(_) => true,
+            Match::Whitelist(_) => false,
+            Match::None => match &self.ignore_files {
+                Some(ignore_files) => ignore_files.is_ignored(&path, is_dir, &levels.0),
+                None => false,
+            },
+        };
+        if !excluded && is_dir {
+            if let Some(ignore_files) = &self.ignore_files {
+                levels.0.push(ignore_files.level(&path, &levels.0));
+            }
+        }
+        excluded
+    }
+}
+
+/// The ignore files of the search path and of the directories below it
+/// down to the parent of an entry
+#[derive(Debug, Clone, Default)]
+pub struct Levels(Vec<Arc<Level>>);
+
+/// The ignore files that apply to the entries being walked, found the way
+/// ripgrep finds them
+///
+/// `.ignore` files always apply. `.gitignore` files, the repository's
+/// `.git/info/exclude` and the global excludes file of git only apply inside
+/// a git repository. When several files match an entry, `.ignore` files win
+/// over `.gitignore` files, which win over the repository and global
+/// excludes, and deeper files win over shallower ones.
+struct IgnoreFiles {
+    /// The directories above the search path, up to its repository root
+    parents: Vec<Arc<Level>>,
+    global: Gitignore,
+}
+
+/// The ignore files of one directory
+#[derive(Debug)]
+pub struct Level {
+    ignore: Option<Gitignore>,
+    gitignore: Option<Gitignore>,
+    git_exclude: Option<Gitignore>,
+    in_repo: bool,
+}
+
+impl Level {
+    fn new(dir: &Path, parent_in_repo: bool) -> Self {
+        let is_repo = dir.join(".git").exists();
+        let in_repo = parent_in_repo || is_repo;
+        Level {
+            ignore: read_ignore_file(dir, ".ignore"),
+            gitignore: in_repo
+                .then(|| read_ignore_file(dir, ".gitignore"))
+                .flatten(),
+            git_exclude: is_repo
+                .then(|| read_ignore_file(dir, ".git/info/exclude"))
+                .flatten(),
+            in_repo,
+        }
+    }
+}
+
+impl IgnoreFiles {
+    fn new(abs_root: &Path) -> Self {
+        // Only the parents within the repository of the search path matter
+        let ancestors: Vec<&Path> = abs_root.ancestors().skip(1).collect();
+        let repo_depth = ancestors.iter().position(|dir| dir.join(".git").exists());
+        let mut parents: Vec<Arc<Level>> = vec![];
+        if let Some(repo_depth) = repo_depth {
+            for dir in ancestors[..=repo_depth].iter().rev() {
+                let in_repo = parents.last().is_some_and(|level| level.in_repo);
+                parents.push(Arc::new(Level::new(dir, in_repo)));
+            }
+        }
+        let (global, err) = Gitignore::global();
+        if let Some(e) = err {
+            eprintln!("{}", e);
+        }
+        IgnoreFiles { parents, global }
+    }
+
+    /// Read the ignore files of the directory `dir` below `levels`
+    fn level(&self, dir: &Path, levels: &[Arc<Level>]) -> Arc<Level> {
+        let in_repo = self
+            .parents
+            .iter()
+            .chain(levels)
+            .last()
+            .is_some_and(|level| level.in_repo);
+        Arc::new(Level::new(dir, in_repo))
+    }
+
+    fn is_ignored(&self, path: &Path, is_dir: bool, levels: &[Arc<Level>]) -> bool {
+        let levels = || self.parents.iter().chain(levels).rev();
+        let matched = |file: fn(&Level) -> Option<&Gitignore>| {
+            levels()
+                .filter_map(|level| file(level))
+                .map(|gitignore| gitignore.matched(path, is_dir))
+                .find(|m| !m.is_none())
+        };
+        let in_repo = levels().next().is_some_and(|level| level.in_repo);
+        let m = matched(|level| level.ignore.as_ref())
+            .or_else(|| matched(|level| level.gitignore.as_ref()))
+            .or_else(|| matched(|level| level.git_exclude.as_ref()))
+            .or_else(|| in_repo.then(|| self.global.matched(path, is_dir)));
+        // The repository itself is not part of its contents
+        let is_git_dir = in_repo && is_dir && path.file_name() == Some(".git".as_ref());
+        is_git_dir || m.is_some_and(|m| m.is_ignore())
+    }
+}
+
+/// Read the ignore file at `name` in `dir`, whose patterns are relative to
+/// `dir`, reporting any error
+fn read_ignore_file(dir: &Path, name: &str) -> Option<Gitignore> {
+    let path = dir.join(name);
+    if !path.is_file() {
+        return None;
+    }
+    let mut builder = GitignoreBuilder::new(dir);
+    if let Some(e) = builder.add(&path) {
+        eprintln!("{}", e);
+    }
+    match builder.build() {
+        Ok(gitignore) => Some(gitignore),
+        Err(e) => {
+            eprintln!("{}", e);
+            None
+        }
+    }
+}
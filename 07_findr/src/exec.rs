@@ -0,0 +1,227 @@
+use std::{
+    ffi::{OsStr, OsString},
+    io::{self, BufRead, Write},
+    mem,
+    path::Path,
+    process::Command,
+};
+
+/// How an `-exec` or `-ok` action runs its command
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mode {
+    /// `-exec CMD ;`: once per entry
+    Each,
+    /// `-exec CMD {} +`: once per batch of entries
+    Batch,
+    /// `-ok CMD ;`: once per entry, if the user agrees
+    Confirm,
+}
+
+/// Whether `arg` ends the command of an `-exec` or `-ok` action, given the
+/// argument before it
+pub fn ends_command(action: &str, arg: &str, prev: Option<&str>) -> bool {
+    arg == ";" || (action == "-exec" && arg == "+" && prev == Some("{}"))
+}
+
+/// A command run on the entries for which an `-exec` or `-ok` action is
+/// evaluated
+#[derive(Debug, Clone)]
+pub struct Exec {
+    /// The command and its arguments, without the final `{}` in batch mode
+    argv: Vec<String>,
+    mode: Mode,
+    batch: Batch,
+    failed: bool,
+}
+
+impl Exec {
+    pub fn new(mut argv: Vec<String>, mode: Mode) -> Self {
+        if mode == Mode::Batch {
+            argv.pop();
+        }
+        let fixed_size = argv.iter().map(|arg| arg_size(arg.as_ref())).sum::<usize>()
+            + std::env::vars_os()
+                .map(|(key, value)| arg_size(&key) + arg_size(&value))
+                .sum::<usize>();
+        // Leave some headroom like xargs does, as the exact accounting of
+        // the limit depends on the system
+        let limit = arg_max().saturating_sub(fixed_size + 2048);
+        Exec {
+            argv,
+            mode,
+            batch: Batch::new(limit),
+            failed: false,
+        }
+    }
+
+    pub fn mode(&self) -> Mode {
+        self.mode
+    }
+
+    /// Run the command for `path`, returning whether it succeeded
+    ///
+    /// In batch mode, `path` is queued and the command only runs once the
+    /// batch is full, so the action is always true.
+    pub fn run(&mut self, path: &Path) -> bool {
+        match self.mode {
+            Mode::Batch => {
+                if let Some(args) = self.batch.push(path.as_os_str().to_owned()) {
+                    self.run_batch(args);
+                }
+                true
+            }
+            Mode::Each => self.spawn(self.substitute(path)),
+            Mode::Confirm => {
+                let args = self.substitute(path);
+                confirm(&self.argv[0], &args) && self.spawn(args)
+            }
+        }
+    }
+
+    /// Run the command on the entries left in the batch, returning whether
+    /// every command run so far succeeded
+    pub fn finish(&mut self) -> bool {
+        let args = self.batch.take();
+        if !args.is_empty() {
+            self.run_batch(args);
+        }
+        !self.failed
+    }
+
+    fn run_batch(&mut self, paths: Vec<OsString>) {
+        let args = self.argv[1..].iter().map(OsString::from).chain(paths);
+        self.spawn(args.collect());
+    }
+
+    /// The arguments of the command, with every `{}` replaced by `path`
+    fn substitute(&self, path: &Path) -> Vec<OsString> {
+        self.argv[1..]
+            .iter()
+            .map(|arg| {
+                let mut parts = arg.split("{}");
+                let mut res = OsString::from(parts.next().unwrap_or_default());
+                for part in parts {
+                    res.push(path);
+                    res.push(part);
+                }
+                res
+            })
+            .collect()
+    }
+
+    /// Run the command with `args`, returning whether it succeeded, and
+    /// setting the exit status if it did not
+    fn spawn(&mut self, args: Vec<OsString>) -> bool {
+        // The command shares our stdout, so what we printed must come first
+        let _ = io::stdout().flush();
+        let success = match Command::new(&self.argv[0]).args(args).status() {
+            Ok(status) => status.success(),
+            Err(e) => {
+                eprintln!("{}: {}", self.argv[0], e);
+                false
+            }
+        };
+        self.failed |= !success;
+        success
+    }
+}
+
+/// Ask the user whether to run `program` with `args`
+fn confirm(program: &str, args: &[OsString]) -> bool {
+    let args: Vec<_> = args.iter().map(|arg| arg.to_string_lossy()).collect();
+    eprint!("< {} {} > ? ", program, args.join(" "));
+    let mut answer = String::new();
+    match io::stdin().lock().read_line(&mut answer) {
+        Ok(_) => answer.trim_start().starts_with(['y', 'Y']),
+        Err(_) => false,
+    }
+}
+
+/// The arguments of a batch command that still fit on its command line
+#[derive(Debug, Clone)]
+struct Batch {
+    args: Vec<OsString>,
+    size: usize,
+    limit: usize,
+}
+
+impl Batch {
+    fn new(limit: usize) -> Self {
+        Batch {
+            args: vec![],
+            size: 0,
+            limit,
+        }
+    }
+
+    /// Add `arg` to the batch, returning the previous arguments if adding
+    /// it would have exceeded the limit
+    fn push(&mut self, arg: OsString) -> Option<Vec<OsString>> {
+        let size = arg_size(&arg);
+        let full = (!self.args.is_empty() && self.size + size > self.limit).then(|| self.take());
+        self.size += size;
+        self.args.push(arg);
+        full
+    }
+
+    fn take(&mut self) -> Vec<OsString> {
+        self.size = 0;
+        mem::take(&mut self.args)
+    }
+}
+
+/// The room taken by `arg` on a command line: its bytes, a terminating nul
+/// and a pointer to it
+fn arg_size(arg: &OsStr) -> usize {
+    arg.len() + 1 + mem::size_of::<usize>()
+}
+
+/// The maximum size of the arguments and environment of a new process
+#[cfg(unix)]
+fn arg_max() -> usize {
+    // SAFETY: sysconf has no preconditions
+    match unsafe { libc::sysconf(libc::_SC_ARG_MAX) } {
+        n if n > 0 => n as usize,
+        _ => 128 * 1024,
+    }
+}
+
+/// The maximum size of a command line on Windows
+#[cfg(not(unix))]
+fn arg_max() -> usize {
+    32 * 1024
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{arg_size, ends_command, Batch};
+    use std::ffi::{OsStr, OsString};
+
+    #[test]
+    fn test_ends_command() {
+        assert!(ends_command("-exec", ";", Some("{}")));
+        assert!(ends_command("-exec", "+", Some("{}")));
+        assert!(!ends_command("-exec", "+", Some("x")));
+        assert!(ends_command("-ok", ";", None));
+        assert!(!ends_command("-ok", "+", Some("{}")));
+    }
+
+    #[test]
+    fn test_batch() {
+        let size = arg_size(OsStr::new("a"));
+        let mut batch = Batch::new(2 * size);
+        assert_eq!(batch.push("a".into()), None);
+        assert_eq!(batch.push("b".into()), None);
+        assert_eq!(
+            batch.push("c".into()),
+            Some(vec![OsString::from("a"), OsString::from("b")])
+        );
+        assert_eq!(batch.take(), vec![OsString::from("c")]);
+        assert_eq!(batch.take(), Vec::<OsString>::new());
+
+        // An argument larger than the limit still gets a batch of its own
+        let mut batch = Batch::new(0);
+        assert_eq!(batch.push("a".into()), None);
+        assert_eq!(batch.push("b".into()), Some(vec![OsString::from("a")]));
+    }
+}
@@ -0,0 +1,446 @@
+use crate::{
+    delete::Delete,
+    entry::Entry,
+    exec::{self, Exec, Mode},
+    predicate::{Age, Comparison, Perm, Size},
+    printf::{self, Format},
+    EntryType, MyResult,
+};
+use clap::ValueEnum;
+use globset::{GlobBuilder, GlobMatcher};
+use regex::{Regex, RegexBuilder};
+#[cfg(unix)]
+use std::os::unix::fs::MetadataExt;
+use std::{
+    ffi::OsStr,
+    fs,
+    io::Write,
+    iter::Peekable,
+    str::FromStr,
+    time::{Duration, SystemTime},
+};
+
+/// A find-style expression made of tests combined with boolean operators
+#[derive(Debug, Clone)]
+pub enum Expr {
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+    Not(Box<Expr>),
+    Name(GlobMatcher),
+    Path(GlobMatcher),
+    Regex(Regex),
+    NameRegex(Regex),
+    Type(Vec<EntryType>),
+    XType(Vec<EntryType>),
+    Size(Size),
+    Modified(Age),
+    Newer(SystemTime),
+    #[cfg(unix)]
+    Perm(Perm),
+    #[cfg(unix)]
+    User(u32),
+    #[cfg(unix)]
+    Group(u32),
+    Empty,
+    Prune,
+    Print,
+    Print0,
+    Printf(Format),
+    Exec(Exec),
+    Delete(Delete),
+}
+
+impl Expr {
+    /// Evaluate the expression, printing to `out` and skipping the
+    /// right-hand side of `-and` and `-or` when the left-hand side already
+    /// decides the result
+    pub fn eval(&mut self, entry: &Entry, out: &mut dyn Write) -> bool {
+        match self {
+            Expr::And(lhs, rhs) => lhs.eval(entry, out) && rhs.eval(entry, out),
+            Expr::Or(lhs, rhs) => lhs.eval(entry, out) || rhs.eval(entry, out),
+            Expr::Not(expr) => !expr.eval(entry, out),
+            Expr::Name(glob) => glob.is_match(entry.file_name()),
+            Expr::Path(glob) => glob.is_match(entry.path()),
+            Expr::Regex(re) => re.is_match(&entry.path().to_string_lossy()),
+            Expr::NameRegex(re) => re.is_match(&entry.file_name().to_string_lossy()),
+            Expr::Type(types) => types.iter().any(|t| t.matches(entry.file_type())),
+            Expr::XType(types) => {
+                let file_type = entry.xtype();
+                types.iter().any(|t| t.matches(file_type))
+            }
+            Expr::Size(size) => entry.metadata().is_some_and(|md| size.matches(md.len())),
+            Expr::Modified(age) => modified(entry).is_some_and(|time| age.matches(time)),
+            Expr::Newer(other) => modified(entry).is_some_and(|time| time > *other),
+            #[cfg(unix)]
+            Expr::Perm(perm) => entry.metadata().is_some_and(|md| perm.matches(md.mode())),
+            #[cfg(unix)]
+            Expr::User(uid) => entry.metadata().is_some_and(|md| md.uid() == *uid),
+            #[cfg(unix)]
+            Expr::Group(gid) => entry.metadata().is_some_and(|md| md.gid() == *gid),
+            Expr::Empty => entry.is_empty(),
+            Expr::Prune => {
+                entry.prune();
+                true
+            }
+            Expr::Print => print(out, entry.path().as_os_str(), b"\n"),
+            Expr::Print0 => print(out, entry.path().as_os_str(), b"\0"),
+            Expr::Printf(format) => write(out, &format.render(entry)),
+            Expr::Exec(exec) => exec.run(entry.path()),
+            Expr::Delete(delete) if delete.is_dry_run() => {
+                print(out, entry.path().as_os_str(), b"\n")
+            }
+            Expr::Delete(delete) => delete.run(entry),
+        }
+    }
+
+    /// Whether `pred` holds for any of the tests and actions of the
+    /// expression
+    pub fn any(&self, pred: fn(&Expr) -> bool) -> bool {
+        match self {
+            Expr::And(lhs, rhs) | Expr::Or(lhs, rhs) => lhs.any(pred) || rhs.any(pred),
+            Expr::Not(expr) => expr.any(pred),
+            _ => pred(self),
+        }
+    }
+
+    /// Whether the expression does something other than testing entries
+    pub fn has_action(&self) -> bool {
+        self.any(|expr| {
+            matches!(
+                expr,
+                Expr::Print | Expr::Print0 | Expr::Printf(_) | Expr::Exec(_) | Expr::Delete(_)
+            )
+        })
+    }
+
+    /// Whether the expression has a test that can filter entries out
+    pub fn has_test(&self) -> bool {
+        self.any(|expr| {
+            !matches!(
+                expr,
+                Expr::Prune
+                    | Expr::Print
+                    | Expr::Print0
+                    | Expr::Printf(_)
+                    | Expr::Exec(_)
+                    | Expr::Delete(_)
+            )
+        })
+    }
+
+    pub fn has_delete(&self) -> bool {
+        self.any(|expr| matches!(expr, Expr::Delete(_)))
+    }
+
+    /// Complete the pending actions once every entry has been evaluated,
+    /// returning whether all the actions succeeded
+    pub fn finish(&mut self) -> bool {
+        match self {
+            Expr::And(lhs, rhs) | Expr::Or(lhs, rhs) => lhs.finish() & rhs.finish(),
+            Expr::Not(expr) => expr.finish(),
+            Expr::Exec(exec) => exec.finish(),
+            Expr::Delete(delete) => delete.finish(),
+            _ => true,
+        }
+    }
+}
+
+/// The expression to evaluate on each entry: `expr`, followed by `-print`
+/// unless it already has an action other than `-prune`
+pub fn with_default_action(expr: Option<Expr>) -> Expr {
+    match expr {
+        None => Expr::Print,
+        Some(expr) if !expr.has_action() => Expr::And(Box::new(expr), Box::new(Expr::Print)),
+        Some(expr) => expr,
+    }
+}
+
+/// Print `path` as raw bytes followed by `terminator`
+fn print(out: &mut dyn Write, path: &OsStr, terminator: &[u8]) -> bool {
+    let mut bytes = printf::os_bytes(path).into_owned();
+    bytes.extend_from_slice(terminator);
+    write(out, &bytes)
+}
+
+fn write(out: &mut dyn Write, bytes: &[u8]) -> bool {
+    match out.write_all(bytes) {
+        Ok(()) => true,
+        Err(e) => {
+            eprintln!("{}", e);
+            false
+        }
+    }
+}
+
+fn modified(entry: &Entry) -> Option<SystemTime> {
+    entry.metadata().and_then(|md| md.modified().ok())
+}
+
+/// Number of arguments taken by an expression token, or `None` if the token
+/// is not part of the expression language
+fn arity(token: &str) -> Option<usize> {
+    match token {
+        "(" | ")" | "!" | "-not" | "-a" | "-and" | "-o" | "-or" => Some(0),
+        "-empty" | "-prune" | "-print" | "-print0" | "-delete" => Some(0),
+        "-name" | "-iname" | "-path" | "-ipath" | "-regex" | "-iregex" | "-name-regex"
+        | "-type" | "-xtype" | "-size" | "-mtime" | "-mmin" | "-newer" | "-perm" | "-user"
+        | "-group" | "-printf" => Some(1),
+        _ => None,
+    }
+}
+
+/// Separate the expression tokens from the options and paths handled by clap
+///
+/// Expression tokens may appear anywhere after the program name, each one
+/// followed by the arguments it takes, which for `-exec` and `-ok` run up to
+/// the end of the command. `-xdev`, which find takes among the expression,
+/// is handed to clap as `--xdev`.
+pub fn split_args<I>(args: I) -> (Vec<String>, Vec<String>)
+where
+    I: IntoIterator<Item = String>,
+{
+    let mut args = args.into_iter();
+    let mut opts: Vec<String> = args.next().into_iter().collect();
+    let mut expr = Vec::new();
+    while let Some(arg) = args.next() {
+        if arg == "--" {
+            opts.push(arg);
+            opts.extend(args.by_ref());
+        } else if arg == "-xdev" {
+            opts.push("--xdev".to_string());
+        } else if arg == "-exec" || arg == "-ok" {
+            let mut prev = None;
+            let action = arg.clone();
+            expr.push(arg);
+            for arg in args.by_ref() {
+                let end = exec::ends_command(&action, &arg, prev.as_deref());
+                expr.push(arg.clone());
+                if end {
+                    break;
+                }
+                prev = Some(arg);
+            }
+        } else if let Some(n) = arity(&arg) {
+            expr.push(arg);
+            expr.extend(args.by_ref().take(n));
+        } else {
+            opts.push(arg);
+        }
+    }
+    (opts, expr)
+}
+
+/// Parse expression tokens into an `Expr`, or `None` if there are no tokens,
+/// where `-delete` only lists entries if `dry_run` is set
+///
+/// Operators bind as in find: `!`/`-not` binds tightest, then `-and` (which
+/// is also implied between two adjacent tests), then `-or`.
+pub fn parse(tokens: &[String], dry_run: bool) -> MyResult<Option<Expr>> {
+    if tokens.is_empty() {
+        return Ok(None);
+    }
+    let mut parser = Parser {
+        tokens: tokens.iter().map(String::as_str).peekable(),
+        now: SystemTime::now(),
+        dry_run,
+    };
+    let expr = parser.parse_or()?;
+    match parser.tokens.next() {
+        None => Ok(Some(expr)),
+        Some(")") => Err("unexpected ')' without a matching '('".into()),
+        Some(token) => Err(format!("unexpected '{}' in expression", token).into()),
+    }
+}
+
+struct Parser<'a, I: Iterator<Item = &'a str>> {
+    tokens: Peekable<I>,
+    /// The time against which `-mtime` and `-mmin` measure ages
+    now: SystemTime,
+    dry_run: bool,
+}
+
+impl<'a, I: Iterator<Item = &'a str>> Parser<'a, I> {
+    fn parse_or(&mut self) -> MyResult<Expr> {
+        let mut expr = self.parse_and()?;
+        while let Some(&op) = self.tokens.peek() {
+            if op != "-o" && op != "-or" {
+                break;
+            }
+            self.tokens.next();
+            let rhs = self.parse_operand(op, Self::parse_and)?;
+            expr = Expr::Or(Box::new(expr), Box::new(rhs));
+        }
+        Ok(expr)
+    }
+
+    fn parse_and(&mut self) -> MyResult<Expr> {
+        let mut expr = self.parse_not()?;
+        while let Some(&op) = self.tokens.peek() {
+            let rhs = match op {
+                "-a" | "-and" => {
+                    self.tokens.next();
+                    self.parse_operand(op, Self::parse_not)?
+                }
+                "-o" | "-or" | ")" => break,
+                _ => self.parse_not()?,
+            };
+            expr = Expr::And(Box::new(expr), Box::new(rhs));
+        }
+        Ok(expr)
+    }
+
+    fn parse_not(&mut self) -> MyResult<Expr> {
+        match self.tokens.peek() {
+            Some(&op) if op == "!" || op == "-not" => {
+                self.tokens.next();
+                let expr = self.parse_operand(op, Self::parse_not)?;
+                Ok(Expr::Not(Box::new(expr)))
+            }
+            _ => self.parse_primary(),
+        }
+    }
+
+    /// Parse the operand of `op`, reporting a missing one instead of letting
+    /// the next parser complain about an unrelated token
+    fn parse_operand(
+        &mut self,
+        op: &str,
+        parse: fn(&mut Self) -> MyResult<Expr>,
+    ) -> MyResult<Expr> {
+        match self.tokens.peek() {
+            None | Some(&"-a" | &"-and" | &"-o" | &"-or" | &")") => {
+                Err(format!("expected an expression after '{}'", op).into())
+            }
+            _ => parse(self),
+        }
+    }
+
+    fn parse_primary(&mut self) -> MyResult<Expr> {
+        let token = self.tokens.next().ok_or("expected an expression")?;
+        match token {
+            "(" => {
+                let expr = self.parse_operand("(", Self::parse_or)?;
+                match self.tokens.next() {
+                    Some(")") => Ok(expr),
+                    _ => Err("missing ')' in expression".into()),
+                }
+            }
+            "-name" | "-iname" => Ok(Expr::Name(self.glob(token)?)),
+            "-path" | "-ipath" => Ok(Expr::Path(self.glob(token)?)),
+            "-regex" | "-iregex" => {
+                let value = self.value(token)?;
+                let re = RegexBuilder::new(&format!("^(?:{})$", value))
+                    .case_insensitive(token == "-iregex")
+                    .build()
+                    .map_err(|e| format!("invalid value '{}' for '{}': {}", value, token, e))?;
+                Ok(Expr::Regex(re))
+            }
+            "-name-regex" => {
+                let value = self.value(token)?;
+                let re = Regex::new(value)
+                    .map_err(|e| format!("invalid value '{}' for '{}': {}", value, token, e))?;
+                Ok(Expr::NameRegex(re))
+            }
+            "-type" | "-xtype" => {
+                let value = self.value(token)?;
+                let types = value
+                    .split(',')
+                    .map(|t| EntryType::from_str(t, false))
+                    .collect::<Result<_, _>>()
+                    .map_err(|_| format!("invalid value '{}' for '{}'", value, token))?;
+                Ok(if token == "-type" {
+                    Expr::Type(types)
+                } else {
+                    Expr::XType(types)
+                })
+            }
+            "-size" => Ok(Expr::Size(self.parse_value(token)?)),
+            "-mtime" | "-mmin" => {
+                let cmp: Comparison = self.parse_value(token)?;
+                let period = if token == "-mtime" { 24 * 60 * 60 } else { 60 };
+                let age = Age::new(cmp, Duration::from_secs(period), self.now);
+                Ok(Expr::Modified(age))
+            }
+            "-newer" => {
+                let path = self.value(token)?;
+                let time = fs::metadata(path)
+                    .and_then(|md| md.modified())
+                    .map_err(|e| format!("{}: {}", path, e))?;
+                Ok(Expr::Newer(time))
+            }
+            #[cfg(unix)]
+            "-perm" => Ok(Expr::Perm(self.parse_value(token)?)),
+            #[cfg(unix)]
+            "-user" => {
+                let value = self.value(token)?;
+                users::get_user_by_name(value)
+                    .map(|user| user.uid())
+                    .or_else(|| value.parse().ok())
+                    .map(Expr::User)
+                    .ok_or_else(|| format!("'-user': no such user '{}'", value).into())
+            }
+            #[cfg(unix)]
+            "-group" => {
+                let value = self.value(token)?;
+                users::get_group_by_name(value)
+                    .map(|group| group.gid())
+                    .or_else(|| value.parse().ok())
+                    .map(Expr::Group)
+                    .ok_or_else(|| format!("'-group': no such group '{}'", value).into())
+            }
+            "-empty" => Ok(Expr::Empty),
+            "-prune" => Ok(Expr::Prune),
+            "-print" => Ok(Expr::Print),
+            "-print0" => Ok(Expr::Print0),
+            "-delete" => Ok(Expr::Delete(Delete::new(self.dry_run))),
+            "-printf" => Ok(Expr::Printf(self.parse_value(token)?)),
+            "-exec" | "-ok" => {
+                let mut argv: Vec<String> = vec![];
+                let terminator = loop {
+                    let arg = self.value(token)?;
+                    if exec::ends_command(token, arg, argv.last().map(String::as_str)) {
+                        break arg;
+                    }
+                    argv.push(arg.to_string());
+                };
+                let mode = match (token, terminator) {
+                    ("-ok", _) => Mode::Confirm,
+                    (_, "+") => Mode::Batch,
+                    _ => Mode::Each,
+                };
+                // The final {} of a batch is not part of the command
+                if argv.len() <= usize::from(mode == Mode::Batch) {
+                    return Err(format!("missing argument to '{}'", token).into());
+                }
+                Ok(Expr::Exec(Exec::new(argv, mode)))
+            }
+            _ => Err(format!("expected an expression before '{}'", token).into()),
+        }
+    }
+
+    /// The argument of the test `token`
+    fn value(&mut self, token: &str) -> MyResult<&'a str> {
+        self.tokens
+            .next()
+            .ok_or_else(|| format!("missing argument to '{}'", token).into())
+    }
+
+    /// The argument of the test `token` as a glob, which ignores case if the
+    /// test name starts with `-i`
+    fn glob(&mut self, token: &str) -> MyResult<GlobMatcher> {
+        let value = self.value(token)?;
+        let glob = GlobBuilder::new(value)
+            .case_insensitive(token.starts_with("-i"))
+            .build()
+            .map_err(|e| format!("invalid value '{}' for '{}': {}", value, token, e))?;
+        Ok(glob.compile_matcher())
+    }
+
+    /// The argument of the test `token`, parsed into a `T`
+    fn parse_value<T: FromStr<Err = String>>(&mut self, token: &str) -> MyResult<T> {
+        let value = self.value(token)?;
+        value
+            .parse()
+            .map_err(|e| format!("invalid value '{}' for '{}': {}", value, token, e).into())
+    }
+}
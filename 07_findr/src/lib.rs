@@ -1,12 +1,34 @@
+mod delete;
+mod entry;
+mod exclude;
+mod exec;
+mod expr;
+mod output;
+mod parallel;
+mod predicate;
+mod printf;
+
 use clap::{Parser, ValueEnum};
+use entry::Entry;
+use exclude::{Exclusions, Levels};
+use exec::Mode;
+use expr::Expr;
+use output::Output;
 use regex::Regex;
-use std::error::Error;
+use std::{cell::RefCell, error::Error, fs, fs::FileType, num::NonZeroUsize, path::Path, thread};
 use walkdir::{DirEntry, WalkDir};
 
 type MyResult<T> = Result<T, Box<dyn Error>>;
 
 #[derive(Debug, Parser)]
-#[command(author, version, about, long_about = None)]
+#[command(
+    author,
+    version,
+    about,
+    long_about = None,
+    override_usage = "findr [OPTIONS] [PATH]... [EXPRESSION]",
+    after_help = EXPRESSION_HELP
+)]
 pub struct Cli {
     #[arg(help = "Search paths", default_value = ".")]
     path: Vec<String>,
@@ -14,50 +36,336 @@ pub struct Cli {
     #[arg(short, long, help = "Name", num_args(0..))]
     name: Vec<Regex>,
 
-    #[arg(short = 't', long = "type", help = "Entry type", value_name = "TYPE", num_args(0..))]
+    #[arg(
+        short = 't',
+        long = "type",
+        help = "Entry type",
+        value_name = "TYPE",
+        num_args(0..),
+        value_delimiter = ','
+    )]
     entry_type: Vec<EntryType>,
+
+    #[arg(long, help = "Descend at most DEPTH levels", value_name = "DEPTH")]
+    max_depth: Option<usize>,
+
+    #[arg(long, help = "Ignore entries above DEPTH", value_name = "DEPTH")]
+    min_depth: Option<usize>,
+
+    #[arg(
+        long,
+        help = "Skip entries matching GLOB, a gitignore pattern",
+        value_name = "GLOB"
+    )]
+    exclude: Vec<String>,
+
+    #[arg(
+        long,
+        help = "Skip entries ignored by .gitignore, .ignore or global excludes files"
+    )]
+    gitignore: bool,
+
+    #[arg(
+        short = 'P',
+        help = "Never follow symbolic links (default)",
+        overrides_with_all = ["follow", "follow_paths"]
+    )]
+    no_follow: bool,
+
+    #[arg(
+        short = 'L',
+        help = "Follow symbolic links",
+        overrides_with_all = ["no_follow", "follow_paths"]
+    )]
+    follow: bool,
+
+    #[arg(
+        short = 'H',
+        help = "Follow symbolic links given as search paths",
+        overrides_with_all = ["no_follow", "follow"]
+    )]
+    follow_paths: bool,
+
+    #[arg(
+        long,
+        help = "Do not descend into directories on other file systems (also -xdev)"
+    )]
+    xdev: bool,
+
+    #[arg(long, help = "Allow -delete without a test, deleting every entry")]
+    force: bool,
+
+    #[arg(long, help = "Make -delete list the entries instead of deleting them")]
+    dry_run: bool,
+
+    #[arg(
+        long,
+        help = "Walk directories with NUM threads, or one per CPU if NUM is 0 [default: 1]",
+        value_name = "NUM"
+    )]
+    threads: Option<usize>,
+
+    #[arg(long, help = "Print the output sorted by path once the walk is done")]
+    sort: bool,
+
+    #[arg(skip)]
+    expr: Option<Expr>,
 }
 
+const EXPRESSION_HELP: &str = "\
+Expression:
+  Tests are combined with operators, from highest to lowest precedence:
+    ( EXPR )                 Grouping
+    ! EXPR, -not EXPR        True if EXPR is false
+    EXPR EXPR, EXPR -a EXPR  Also -and; EXPR2 is skipped if EXPR1 is false
+    EXPR -o EXPR             Also -or; EXPR2 is skipped if EXPR1 is true
+  Tests (N is +N for more than N, -N for less than N or N for exactly N):
+    -name GLOB               File name matches GLOB, where * matches any string,
+                             ? any character and [...] any character listed
+    -iname GLOB              Like -name, ignoring case
+    -path GLOB               Path matches GLOB, where * and ? also match /
+    -ipath GLOB              Like -path, ignoring case
+    -regex REGEX             Whole path matches REGEX
+    -iregex REGEX            Like -regex, ignoring case
+    -name-regex REGEX        File name contains a match of REGEX, like --name
+    -type TYPES              Entry is of one of the comma-separated TYPES: f (file),
+                             d (directory), l (link), p (FIFO), s (socket),
+                             b (block device) or c (character device)
+    -xtype TYPES             Like -type, but for the target of a link that is
+                             not followed, or the link itself if it is
+    -size N[bcwkMG]          Size is N units, rounded up (default: 512-byte blocks)
+    -mtime N                 Modified N days ago, ignoring fractions of a day
+    -mmin N                  Modified N minutes ago, ignoring fractions of a minute
+    -newer FILE              Modified more recently than FILE
+    -perm MODE               Permission bits are exactly MODE (octal or symbolic)
+    -perm -MODE              All of the permission bits of MODE are set
+    -perm /MODE              Any of the permission bits of MODE is set
+    -user NAME               Owned by user NAME or user ID NAME
+    -group NAME              Owned by group NAME or group ID NAME
+    -empty                   Empty regular file or directory
+    -prune                   Do not descend into the directory, and is true
+  Actions (-print is implied if the expression has none besides -prune):
+    -print                   Print the path and a newline, and is true
+    -print0                  Print the path and a null character, and is true
+    -printf FORMAT           Print FORMAT, and is true; FORMAT has the escapes
+                             \\n \\t \\0 \\\\ and the directives %p (path), %f (file
+                             name), %h (parent), %s (size), %m (octal mode),
+                             %u (user), %g (group), %t (modification time),
+                             %y (type), %d (depth) and %% (a %), which may
+                             have a width up to 4096 like %10s or %-10s
+    -exec CMD ;              Run CMD with every {} replaced by the path, and is
+                             true if CMD succeeds
+    -exec CMD {} +           Run CMD with as many paths as fit on the command
+                             line, and is true
+    -ok CMD ;                Like -exec CMD ; after asking for confirmation,
+                             and is false if the answer is no
+    -delete                  Delete the entry, and is true if it succeeds;
+                             directories are then evaluated after their
+                             contents, and -prune has no effect on them
+  The expression is combined with --name and --type using -and. Any CMD that
+  fails or cannot be run makes the exit status 1.";
+
 #[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, ValueEnum)]
 enum EntryType {
     F,
     D,
     L,
+    P,
+    S,
+    B,
+    C,
 }
 
-pub fn get_args() -> MyResult<Cli> {
-    Cli::try_parse().map_err(|e| e.into())
+impl EntryType {
+    fn matches(&self, file_type: FileType) -> bool {
+        let letter = match self {
+            EntryType::F => 'f',
+            EntryType::D => 'd',
+            EntryType::L => 'l',
+            EntryType::P => 'p',
+            EntryType::S => 's',
+            EntryType::B => 'b',
+            EntryType::C => 'c',
+        };
+        printf::type_char(file_type) == letter
+    }
 }
 
-pub fn run(cli: Cli) -> MyResult<()> {
-    let type_filter = |entry: &DirEntry| {
-        cli.entry_type.is_empty()
-            || cli.entry_type.iter().any(|entry_type| match entry_type {
-                EntryType::F => entry.path().is_file() && !entry.path_is_symlink(),
-                EntryType::D => entry.path().is_dir(),
-                EntryType::L => entry.path_is_symlink(),
-            })
-    };
-    let name_filter = |entry: &DirEntry| {
-        cli.name.is_empty()
-            || cli
+impl Cli {
+    /// Whether the expression is to be evaluated on `entry`, which is found
+    /// at or below the minimum depth and matches `--name` and `--type`
+    fn selects(&self, entry: &Entry) -> bool {
+        let type_matches = self.entry_type.is_empty()
+            || self
+                .entry_type
+                .iter()
+                .any(|entry_type| entry_type.matches(entry.file_type()));
+        let name_matches = self.name.is_empty()
+            || self
                 .name
                 .iter()
-                .any(|re| re.is_match(&entry.file_name().to_string_lossy()))
+                .any(|re| re.is_match(&entry.file_name().to_string_lossy()));
+        entry.depth() >= self.min_depth.unwrap_or(0) && type_matches && name_matches
+    }
+
+    fn threads(&self) -> usize {
+        match self.threads {
+            None => 1,
+            Some(0) => thread::available_parallelism().map_or(1, NonZeroUsize::get),
+            Some(n) => n,
+        }
+    }
+}
+
+pub fn get_args() -> MyResult<Cli> {
+    let (args, tokens) = expr::split_args(std::env::args());
+    let mut cli = Cli::try_parse_from(args)?;
+    cli.expr = expr::parse(&tokens, cli.dry_run)?;
+    if let Some(expr) = cli.expr.as_ref().filter(|expr| expr.has_delete()) {
+        if cli.follow {
+            return Err("'-delete' cannot be used with -L".into());
+        }
+        let filtered = expr.has_test() || !cli.name.is_empty() || !cli.entry_type.is_empty();
+        if !filtered && !cli.force {
+            return Err("'-delete' without a test would delete every entry, use --force".into());
+        }
+    }
+    if cli.threads() > 1 {
+        let sequential = |expr: &Expr| match expr {
+            Expr::Delete(_) => true,
+            Expr::Exec(exec) => exec.mode() == Mode::Confirm,
+            _ => false,
+        };
+        if cli.expr.as_ref().is_some_and(|expr| expr.any(sequential)) {
+            return Err("'-delete' and '-ok' cannot be used with --threads".into());
+        }
+        if cfg!(not(unix)) && cli.xdev {
+            return Err("--xdev cannot be used with --threads on this system".into());
+        }
+    }
+    Ok(cli)
+}
+
+/// Run the search, returning the exit status
+pub fn run(mut cli: Cli) -> MyResult<i32> {
+    let expr = expr::with_default_action(cli.expr.take());
+    let mut output = Output::new(cli.sort);
+    let ok = match cli.threads() {
+        1 => walk(&cli, expr, &mut output)?,
+        threads => parallel::walk(&cli, expr, &mut output, threads)?,
     };
+    output.finish();
+    Ok(if ok { 0 } else { 1 })
+}
+
+/// Walk the search paths one after the other, returning whether all the
+/// actions succeeded
+fn walk(cli: &Cli, expr: Expr, output: &mut Output) -> MyResult<bool> {
+    let search = RefCell::new(Search::new(cli, expr, output));
     for path in &cli.path {
-        WalkDir::new(path)
-            .into_iter()
-            .filter_map(|entry| match entry {
-                Ok(entry) => Some(entry),
-                Err(e) => {
-                    eprintln!("{}", e);
-                    None
+        let exclusions = Exclusions::new(path, &cli.exclude, cli.gitignore)?;
+        let mut levels = Levels::default();
+        let mut walker = WalkDir::new(path)
+            .follow_links(cli.follow)
+            .follow_root_links(cli.follow || cli.follow_paths)
+            .same_file_system(cli.xdev);
+        if let Some(depth) = cli.max_depth {
+            walker = walker.max_depth(depth);
+        }
+        let walker = walker.into_iter().filter_entry(|dent| {
+            let is_dir = dent.file_type().is_dir();
+            !exclusions.is_excluded(dent.path(), is_dir, dent.depth(), &mut levels)
+                && search.borrow_mut().visit(dent)
+        });
+        for result in walker {
+            if let Err(e) = result {
+                match broken_link(&e) {
+                    Some((path, file_type)) => {
+                        search
+                            .borrow_mut()
+                            .visit_broken_link(path, e.depth(), file_type)
+                    }
+                    None => eprintln!("{}", e),
                 }
-            })
-            .filter(type_filter)
-            .filter(name_filter)
-            .for_each(|entry| println!("{}", entry.path().display()));
+            }
+        }
+        search.borrow_mut().leave_dirs(0);
+    }
+    Ok(search.into_inner().expr.finish())
+}
+
+/// The evaluation of the expression on the entries of the walks, in the
+/// order of the walk, or with directories after their contents when the
+/// expression deletes entries
+struct Search<'a> {
+    cli: &'a Cli,
+    expr: Expr,
+    output: &'a mut Output,
+    follow_paths: bool,
+    depth_first: bool,
+    /// The directories being walked, whose evaluation waits for the end of
+    /// their contents in a depth-first search
+    pending: Vec<DirEntry>,
+}
+
+impl<'a> Search<'a> {
+    fn new(cli: &'a Cli, expr: Expr, output: &'a mut Output) -> Self {
+        Search {
+            cli,
+            follow_paths: cli.follow || cli.follow_paths,
+            depth_first: expr.has_delete(),
+            expr,
+            output,
+            pending: vec![],
+        }
+    }
+
+    /// Evaluate the entry of `dent`, returning whether to descend into it
+    ///
+    /// Entries must be given in the order of the walk, with directories
+    /// before their contents.
+    fn visit(&mut self, dent: &DirEntry) -> bool {
+        self.leave_dirs(dent.depth());
+        let entry = Entry::new(dent, self.follow_paths);
+        if self.depth_first && entry.file_type().is_dir() {
+            self.pending.push(dent.clone());
+            return true;
+        }
+        self.eval(&entry);
+        !entry.is_pruned()
+    }
+
+    fn visit_broken_link(&mut self, path: &Path, depth: usize, file_type: FileType) {
+        self.leave_dirs(depth);
+        self.eval(&Entry::with_type(path, depth, file_type, false));
+    }
+
+    /// Evaluate the pending directories found at `depth` or deeper, which
+    /// the walk has left
+    fn leave_dirs(&mut self, depth: usize) {
+        while self.pending.last().is_some_and(|dir| dir.depth() >= depth) {
+            if let Some(dir) = self.pending.pop() {
+                self.eval(&Entry::new(&dir, self.follow_paths));
+            }
+        }
+    }
+
+    /// Evaluate the expression on `entry` if it is selected; entries above
+    /// the minimum depth are still walked, so that exclusions apply to them
+    fn eval(&mut self, entry: &Entry) {
+        if self.cli.selects(entry) {
+            self.output.eval(&mut self.expr, entry);
+        }
+    }
+}
+
+/// The path and type of the link if `e` comes from following a broken
+/// symbolic link, which is then an entry in its own right
+fn broken_link(e: &walkdir::Error) -> Option<(&Path, FileType)> {
+    let path = e.path()?;
+    if e.loop_ancestor().is_some() || fs::metadata(path).is_ok() {
+        return None;
     }
-    Ok(())
+    let file_type = fs::symlink_metadata(path).ok()?.file_type();
+    file_type.is_symlink().then_some((path, file_type))
 }
@@ -0,0 +1,71 @@
+use crate::{entry::Entry, expr::Expr};
+use std::{
+    io::{self, Write},
+    mem,
+    path::PathBuf,
+};
+
+/// What the expression printed for an entry, along with the entry's path
+pub type Printed = (PathBuf, Vec<u8>);
+
+/// Where the expression prints: straight to stdout, or into a list that is
+/// printed sorted by path once the search is done
+#[derive(Debug)]
+pub struct Output {
+    kept: Option<Vec<Printed>>,
+}
+
+impl Output {
+    /// An output that keeps what is printed if `keep` is set
+    pub fn new(keep: bool) -> Self {
+        Output {
+            kept: keep.then(Vec::new),
+        }
+    }
+
+    /// Evaluate `expr` on `entry`, returning whether it is true
+    pub fn eval(&mut self, expr: &mut Expr, entry: &Entry) -> bool {
+        match &mut self.kept {
+            None => expr.eval(entry, &mut io::stdout()),
+            Some(kept) => {
+                let mut bytes = vec![];
+                let res = expr.eval(entry, &mut bytes);
+                if !bytes.is_empty() {
+                    kept.push((entry.path().to_path_buf(), bytes));
+                }
+                res
+            }
+        }
+    }
+
+    /// Take what was kept so far
+    pub fn take(&mut self) -> Vec<Printed> {
+        self.kept.as_mut().map(mem::take).unwrap_or_default()
+    }
+
+    /// Add what another output kept, printing it unless this one keeps it too
+    pub fn extend(&mut self, printed: Vec<Printed>) {
+        match &mut self.kept {
+            None => write(printed),
+            Some(kept) => kept.extend(printed),
+        }
+    }
+
+    /// Print what was kept, sorted by path
+    pub fn finish(self) {
+        if let Some(mut kept) = self.kept {
+            kept.sort_by(|(lhs, _), (rhs, _)| lhs.cmp(rhs));
+            write(kept);
+        }
+    }
+}
+
+fn write(printed: Vec<Printed>) {
+    let mut stdout = io::stdout().lock();
+    for (_, bytes) in printed {
+        if let Err(e) = stdout.write_all(&bytes) {
+            eprintln!("{}", e);
+            return;
+        }
+    }
+}
@@ -0,0 +1,360 @@
+use crate::{
+    entry::Entry,
+    exclude::{Exclusions, Levels},
+    expr::Expr,
+    output::{Output, Printed},
+    Cli, MyResult,
+};
+use crossbeam_deque::{Injector, Steal, Stealer, Worker};
+use std::{
+    fmt::Display,
+    fs::{self, FileType},
+    io, iter,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        mpsc::{self, Sender},
+        Condvar, Mutex, PoisonError,
+    },
+    thread,
+};
+
+/// Walk the search paths with `threads` threads, each evaluating its own
+/// copy of `expr`, returning whether all the actions succeeded
+///
+/// Directories are read by whichever thread takes them from a shared queue
+/// or steals them from another thread's queue. What the threads print comes
+/// back to `output` one directory at a time, in no particular order.
+pub fn walk(cli: &Cli, expr: Expr, output: &mut Output, threads: usize) -> MyResult<bool> {
+    let exclusions = cli
+        .path
+        .iter()
+        .map(|path| Exclusions::new(path, &cli.exclude, cli.gitignore))
+        .collect::<MyResult<Vec<_>>>()?;
+    let walk = Walk {
+        cli,
+        injector: Injector::new(),
+        pending: AtomicUsize::new(0),
+        sleeping: AtomicUsize::new(0),
+        lock: Mutex::new(()),
+        wake: Condvar::new(),
+    };
+    // The search paths themselves are evaluated up front, which also
+    // queues the first directories
+    let mut root_expr = expr.clone();
+    for (path, exclusions) in cli.path.iter().zip(&exclusions) {
+        walk.visit_root(Path::new(path), exclusions, &mut root_expr, output);
+    }
+
+    let workers: Vec<_> = (0..threads).map(|_| Worker::new_lifo()).collect();
+    let stealers: Vec<_> = workers.iter().map(Worker::stealer).collect();
+    let (tx, rx) = mpsc::channel();
+    let exprs: Vec<Expr> = thread::scope(|scope| {
+        let handles: Vec<_> = workers
+            .into_iter()
+            .map(|local| {
+                let (walk, stealers, expr, tx) = (&walk, &stealers, expr.clone(), tx.clone());
+                scope.spawn(move || walk.work(local, stealers, expr, tx))
+            })
+            .collect();
+        drop(tx);
+        for printed in rx {
+            output.extend(printed);
+        }
+        handles
+            .into_iter()
+            .map(|handle| {
+                handle
+                    .join()
+                    .unwrap_or_else(|e| std::panic::resume_unwind(e))
+            })
+            .collect()
+    });
+    Ok(iter::once(root_expr)
+        .chain(exprs)
+        .fold(true, |ok, mut expr| expr.finish() & ok))
+}
+
+/// The state of a walk shared by its threads
+struct Walk<'a> {
+    cli: &'a Cli,
+    /// The directories not yet taken by any thread
+    injector: Injector<Dir<'a>>,
+    /// The number of directories queued or being read
+    pending: AtomicUsize,
+    /// The number of threads waiting on `wake` for something to do
+    sleeping: AtomicUsize,
+    lock: Mutex<()>,
+    /// Signalled when a directory is queued or the walk is done
+    wake: Condvar,
+}
+
+/// A directory whose contents are to be walked
+struct Dir<'a> {
+    path: PathBuf,
+    depth: usize,
+    exclusions: &'a Exclusions,
+    levels: Levels,
+    /// The directory and those above it, when following links
+    ancestors: Vec<(FileId, PathBuf)>,
+    /// The device of the search path, when not crossing file systems
+    device: Option<u64>,
+}
+
+impl<'a> Walk<'a> {
+    /// Evaluate a search path and queue it if it is a directory to walk
+    fn visit_root(
+        &self,
+        path: &Path,
+        exclusions: &'a Exclusions,
+        expr: &mut Expr,
+        output: &mut Output,
+    ) {
+        let (file_type, followed) = match file_type(path, self.cli.follow || self.cli.follow_paths)
+        {
+            Ok(res) => res,
+            Err(e) => return report(path, e),
+        };
+        let mut levels = Levels::default();
+        exclusions.is_excluded(path, file_type.is_dir(), 0, &mut levels);
+        let entry = Entry::with_type(path, 0, file_type, followed);
+        self.eval(&entry, expr, output);
+        if !file_type.is_dir() || entry.is_pruned() || self.cli.max_depth == Some(0) {
+            return;
+        }
+        let id = match file_id(path) {
+            Ok(id) => id,
+            Err(e) => return report(path, e),
+        };
+        let device = self.cli.xdev.then(|| device(&id));
+        self.pending.fetch_add(1, Ordering::SeqCst);
+        self.injector.push(Dir {
+            path: path.to_path_buf(),
+            depth: 0,
+            exclusions,
+            levels,
+            ancestors: if self.cli.follow {
+                vec![(id, path.to_path_buf())]
+            } else {
+                vec![]
+            },
+            device,
+        });
+    }
+
+    /// Read directories until there are none left in any queue
+    fn work(
+        &self,
+        local: Worker<Dir<'a>>,
+        stealers: &[Stealer<Dir<'a>>],
+        mut expr: Expr,
+        tx: Sender<Vec<Printed>>,
+    ) -> Expr {
+        let mut output = Output::new(true);
+        loop {
+            match self.find_dir(&local, stealers) {
+                Some(dir) => {
+                    self.read_dir(dir, &local, &mut expr, &mut output);
+                    // The directories found in it were counted first, so
+                    // the count only drops to zero once the walk is done
+                    if self.pending.fetch_sub(1, Ordering::SeqCst) == 1 {
+                        self.notify(|wake| wake.notify_all());
+                    }
+                    let printed = output.take();
+                    if !printed.is_empty() {
+                        let _ = tx.send(printed);
+                    }
+                }
+                None if self.pending.load(Ordering::SeqCst) == 0 => break,
+                None => self.sleep(stealers),
+            }
+        }
+        expr
+    }
+
+    /// Wait until a directory is queued or the walk is done, unless that
+    /// already happened
+    fn sleep(&self, stealers: &[Stealer<Dir<'a>>]) {
+        let guard = self.lock.lock().unwrap_or_else(PoisonError::into_inner);
+        // Once counted, whoever queues a directory or ends the walk has to
+        // take the lock to notify, which it only gets once this waits
+        self.sleeping.fetch_add(1, Ordering::SeqCst);
+        let idle = self.injector.is_empty() && stealers.iter().all(Stealer::is_empty);
+        if idle && self.pending.load(Ordering::SeqCst) > 0 {
+            let _guard = self
+                .wake
+                .wait(guard)
+                .unwrap_or_else(PoisonError::into_inner);
+        }
+        self.sleeping.fetch_sub(1, Ordering::SeqCst);
+    }
+
+    /// Wake the sleeping threads with `notify`, if there are any
+    fn notify(&self, notify: impl FnOnce(&Condvar)) {
+        if self.sleeping.load(Ordering::SeqCst) > 0 {
+            let _guard = self.lock.lock().unwrap_or_else(PoisonError::into_inner);
+            notify(&self.wake);
+        }
+    }
+
+    /// The next directory from the local queue, or else from the shared
+    /// queue or another thread's queue
+    fn find_dir(&self, local: &Worker<Dir<'a>>, stealers: &[Stealer<Dir<'a>>]) -> Option<Dir<'a>> {
+        local.pop().or_else(|| {
+            iter::repeat_with(|| {
+                self.injector
+                    .steal_batch_and_pop(local)
+                    .or_else(|| stealers.iter().map(Stealer::steal).collect())
+            })
+            .find(|steal| !steal.is_retry())
+            .and_then(Steal::success)
+        })
+    }
+
+    /// Evaluate the entries of `dir`, queueing the directories among them
+    fn read_dir(
+        &self,
+        dir: Dir<'a>,
+        local: &Worker<Dir<'a>>,
+        expr: &mut Expr,
+        output: &mut Output,
+    ) {
+        let entries = match fs::read_dir(&dir.path) {
+            Ok(entries) => entries,
+            Err(e) => return report(&dir.path, e),
+        };
+        let depth = dir.depth + 1;
+        for result in entries {
+            let dent = match result {
+                Ok(dent) => dent,
+                Err(e) => {
+                    report(&dir.path, e);
+                    continue;
+                }
+            };
+            let path = dent.path();
+            let (file_type, followed) = match dent.file_type() {
+                Ok(file_type) if file_type.is_symlink() && self.cli.follow => {
+                    match fs::metadata(&path) {
+                        Ok(metadata) => (metadata.file_type(), true),
+                        Err(_) => (file_type, false),
+                    }
+                }
+                Ok(file_type) => (file_type, false),
+                Err(e) => {
+                    report(&path, e);
+                    continue;
+                }
+            };
+            let is_dir = file_type.is_dir();
+            let mut levels = dir.levels.clone();
+            if dir
+                .exclusions
+                .is_excluded(&path, is_dir, depth, &mut levels)
+            {
+                continue;
+            }
+            let id = if is_dir && (self.cli.follow || self.cli.xdev) {
+                match file_id(&path) {
+                    Ok(id) => Some(id),
+                    Err(e) => {
+                        report(&path, e);
+                        continue;
+                    }
+                }
+            } else {
+                None
+            };
+            // Like walkdir, report a loop instead of the entry
+            if let Some(id) = id.as_ref().filter(|_| self.cli.follow) {
+                if let Some((_, ancestor)) = dir.ancestors.iter().find(|(other, _)| other == id) {
+                    eprintln!(
+                        "File system loop found: {} points to an ancestor {}",
+                        path.display(),
+                        ancestor.display()
+                    );
+                    continue;
+                }
+            }
+
+            let entry = Entry::with_type(&path, depth, file_type, followed);
+            self.eval(&entry, expr, output);
+            let descend = is_dir
+                && !entry.is_pruned()
+                && self.cli.max_depth.is_none_or(|max_depth| depth < max_depth)
+                && dir
+                    .device
+                    .is_none_or(|device| id.as_ref().map(self::device) == Some(device));
+            if !descend {
+                continue;
+            }
+            let mut ancestors = dir.ancestors.clone();
+            if let Some(id) = id.filter(|_| self.cli.follow) {
+                ancestors.push((id, path.clone()));
+            }
+            self.pending.fetch_add(1, Ordering::SeqCst);
+            local.push(Dir {
+                path,
+                depth,
+                exclusions: dir.exclusions,
+                levels,
+                ancestors,
+                device: dir.device,
+            });
+            self.notify(|wake| wake.notify_one());
+        }
+    }
+
+    fn eval(&self, entry: &Entry, expr: &mut Expr, output: &mut Output) {
+        if self.cli.selects(entry) {
+            output.eval(expr, entry);
+        }
+    }
+}
+
+/// The type of the entry at `path`, or of its target if `follow` is set and
+/// it is a link that is not broken, and whether it was followed
+fn file_type(path: &Path, follow: bool) -> io::Result<(FileType, bool)> {
+    let file_type = fs::symlink_metadata(path)?.file_type();
+    if follow && file_type.is_symlink() {
+        if let Ok(metadata) = fs::metadata(path) {
+            return Ok((metadata.file_type(), true));
+        }
+    }
+    Ok((file_type, false))
+}
+
+/// Report an error about `path` the way walkdir does
+fn report(path: &Path, e: impl Display) {
+    eprintln!("IO error for operation on {}: {}", path.display(), e);
+}
+
+/// What identifies a directory, to detect links back to an ancestor
+#[cfg(unix)]
+type FileId = (u64, u64);
+
+#[cfg(unix)]
+fn file_id(path: &Path) -> io::Result<FileId> {
+    use std::os::unix::fs::MetadataExt;
+    let metadata = fs::metadata(path)?;
+    Ok((metadata.dev(), metadata.ino()))
+}
+
+#[cfg(unix)]
+fn device(id: &FileId) -> u64 {
+    id.0
+}
+
+#[cfg(not(unix))]
+type FileId = PathBuf;
+
+#[cfg(not(unix))]
+fn file_id(path: &Path) -> io::Result<FileId> {
+    fs::canonicalize(path)
+}
+
+/// Devices are not known here, so `--xdev` cannot be used with `--threads`
+#[cfg(not(unix))]
+fn device(_id: &FileId) -> u64 {
+    0
+}
@@ -0,0 +1,242 @@
+use std::{
+    str::FromStr,
+    time::{Duration, SystemTime},
+};
+
+/// A numeric argument: `+N` for more than N, `-N` for less than N and `N`
+/// for exactly N
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Comparison {
+    Less(u64),
+    Equal(u64),
+    Greater(u64),
+}
+
+impl Comparison {
+    pub fn matches(&self, value: u64) -> bool {
+        match *self {
+            Comparison::Less(n) => value < n,
+            Comparison::Equal(n) => value == n,
+            Comparison::Greater(n) => value > n,
+        }
+    }
+
+    /// Split the sign off `s`, returning the comparison constructor and the
+    /// rest of the string
+    fn split_sign(s: &str) -> (fn(u64) -> Comparison, &str) {
+        if let Some(rest) = s.strip_prefix('+') {
+            (Comparison::Greater, rest)
+        } else if let Some(rest) = s.strip_prefix('-') {
+            (Comparison::Less, rest)
+        } else {
+            (Comparison::Equal, s)
+        }
+    }
+}
+
+impl FromStr for Comparison {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (cmp, digits) = Comparison::split_sign(s);
+        if digits.is_empty() || !digits.chars().all(|c| c.is_ascii_digit()) {
+            return Err(format!("invalid number '{}'", s));
+        }
+        digits
+            .parse()
+            .map(cmp)
+            .map_err(|e| format!("invalid number '{}': {}", s, e))
+    }
+}
+
+/// A `-size` argument: a comparison on the size rounded up to whole units
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Size {
+    cmp: Comparison,
+    unit: u64,
+}
+
+impl Size {
+    pub fn matches(&self, len: u64) -> bool {
+        self.cmp.matches(len.div_ceil(self.unit))
+    }
+}
+
+impl FromStr for Size {
+    type Err = String;
+
+    /// Parse `[+-]N[bckMG]`, where the unit defaults to 512-byte blocks
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (n, unit) = match s.char_indices().last() {
+            Some((i, c)) if c.is_ascii_alphabetic() => (&s[..i], c),
+            _ => (s, 'b'),
+        };
+        let unit = match unit {
+            'b' => 512,
+            'c' => 1,
+            'w' => 2,
+            'k' => 1 << 10,
+            'M' => 1 << 20,
+            'G' => 1 << 30,
+            _ => return Err(format!("invalid size unit '{}'", unit)),
+        };
+        let cmp = n.parse().map_err(|_| format!("invalid size '{}'", s))?;
+        Ok(Size { cmp, unit })
+    }
+}
+
+/// A `-mtime` or `-mmin` argument: a comparison on the number of whole
+/// periods elapsed between a time and the start of the search
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Age {
+    cmp: Comparison,
+    period: Duration,
+    now: SystemTime,
+}
+
+impl Age {
+    pub fn new(cmp: Comparison, period: Duration, now: SystemTime) -> Self {
+        Age { cmp, period, now }
+    }
+
+    pub fn matches(&self, time: SystemTime) -> bool {
+        let age = self.now.duration_since(time).unwrap_or_default();
+        self.cmp.matches(age.as_secs() / self.period.as_secs())
+    }
+}
+
+/// A `-perm` argument
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Perm {
+    /// `MODE`: the permission bits are exactly MODE
+    Exact(u32),
+    /// `-MODE`: all the bits of MODE are set
+    All(u32),
+    /// `/MODE`: any of the bits of MODE is set, or MODE is 0
+    Any(u32),
+}
+
+impl Perm {
+    pub fn matches(&self, mode: u32) -> bool {
+        let mode = mode & 0o7777;
+        match *self {
+            Perm::Exact(bits) => mode == bits,
+            Perm::All(bits) => mode & bits == bits,
+            Perm::Any(bits) => bits == 0 || mode & bits != 0,
+        }
+    }
+}
+
+impl FromStr for Perm {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (perm, mode): (fn(u32) -> Perm, _) = if let Some(mode) = s.strip_prefix('-') {
+            (Perm::All, mode)
+        } else if let Some(mode) = s.strip_prefix('/') {
+            (Perm::Any, mode)
+        } else {
+            (Perm::Exact, s)
+        };
+        parse_mode(mode)
+            .map(perm)
+            .ok_or_else(|| format!("invalid mode '{}'", s))
+    }
+}
+
+/// Parse an octal mode like `644` or a symbolic one like `u+rwx,go=rx`,
+/// applied to a mode with no bits set
+fn parse_mode(s: &str) -> Option<u32> {
+    if s.chars().all(|c| c.is_digit(8)) {
+        return u32::from_str_radix(s, 8)
+            .ok()
+            .filter(|mode| *mode <= 0o7777);
+    }
+    let mut mode = 0;
+    for clause in s.split(',') {
+        let op_index = clause.find(['+', '-', '='])?;
+        let (who, rest) = clause.split_at(op_index);
+        let mut who_mask = 0;
+        for c in who.chars() {
+            who_mask |= match c {
+                'u' => 0o4700,
+                'g' => 0o2070,
+                'o' => 0o1007,
+                'a' => 0o7777,
+                _ => return None,
+            };
+        }
+        if who_mask == 0 {
+            who_mask = 0o7777;
+        }
+        let (op, perms) = rest.split_at(1);
+        let mut bits = 0;
+        for c in perms.chars() {
+            bits |= match c {
+                'r' => 0o444,
+                'w' => 0o222,
+                'x' => 0o111,
+                's' => 0o6000,
+                't' => 0o1000,
+                _ => return None,
+            };
+        }
+        bits &= who_mask;
+        match op {
+            "+" => mode |= bits,
+            "-" => mode &= !bits,
+            _ => mode = (mode & !who_mask) | bits,
+        }
+    }
+    Some(mode)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Comparison, Perm, Size};
+
+    #[test]
+    fn test_parse_comparison() {
+        assert_eq!("3".parse(), Ok(Comparison::Equal(3)));
+        assert_eq!("+3".parse(), Ok(Comparison::Greater(3)));
+        assert_eq!("-3".parse(), Ok(Comparison::Less(3)));
+        assert!("".parse::<Comparison>().is_err());
+        assert!("+-3".parse::<Comparison>().is_err());
+        assert!("3x".parse::<Comparison>().is_err());
+    }
+
+    #[test]
+    fn test_size() {
+        let size: Size = "-1k".parse().unwrap();
+        assert!(size.matches(0));
+        assert!(!size.matches(1));
+        let size: Size = "+2c".parse().unwrap();
+        assert!(!size.matches(2));
+        assert!(size.matches(3));
+        let size: Size = "1".parse().unwrap();
+        assert!(size.matches(1));
+        assert!(size.matches(512));
+        assert!(!size.matches(513));
+        assert!("1x".parse::<Size>().is_err());
+        assert!("k".parse::<Size>().is_err());
+    }
+
+    #[test]
+    fn test_perm() {
+        assert_eq!("644".parse(), Ok(Perm::Exact(0o644)));
+        assert_eq!("-u+x".parse(), Ok(Perm::All(0o100)));
+        assert_eq!("/go=w".parse(), Ok(Perm::Any(0o022)));
+        assert_eq!("a=r,u+w".parse(), Ok(Perm::Exact(0o644)));
+        assert_eq!("+x".parse(), Ok(Perm::Exact(0o111)));
+        assert!("999".parse::<Perm>().is_err());
+        assert!("u+q".parse::<Perm>().is_err());
+
+        assert!(Perm::Exact(0o644).matches(0o100644));
+        assert!(!Perm::Exact(0o644).matches(0o100755));
+        assert!(Perm::All(0o111).matches(0o755));
+        assert!(!Perm::All(0o111).matches(0o744));
+        assert!(Perm::Any(0o111).matches(0o744));
+        assert!(!Perm::Any(0o111).matches(0o644));
+        assert!(Perm::Any(0).matches(0o644));
+    }
+}
@@ -0,0 +1,250 @@
+use crate::entry::Entry;
+use chrono::{DateTime, Local};
+use std::{borrow::Cow, ffi::OsStr, fs::FileType, path::Path, str::FromStr};
+
+/// The widest a directive can be padded, which keeps the padding of every
+/// entry from taking all the memory
+const MAX_WIDTH: usize = 4096;
+
+/// A `-printf` format: text with `\` escapes and `%` directives
+#[derive(Debug, Clone)]
+pub struct Format(Vec<Piece>);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Piece {
+    Literal(Vec<u8>),
+    Directive {
+        directive: char,
+        width: usize,
+        left_align: bool,
+    },
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut pieces = vec![];
+        let mut literal = vec![];
+        let mut chars = s.chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                '\\' => {
+                    let escaped = match chars.next() {
+                        Some('n') => '\n',
+                        Some('t') => '\t',
+                        Some('r') => '\r',
+                        Some('0') => '\0',
+                        Some('a') => '\x07',
+                        Some('b') => '\x08',
+                        Some('f') => '\x0c',
+                        Some('v') => '\x0b',
+                        Some('\\') => '\\',
+                        Some(c) => return Err(format!("unknown escape '\\{}'", c)),
+                        None => return Err("trailing '\\'".to_string()),
+                    };
+                    literal.extend_from_slice(escaped.encode_utf8(&mut [0; 4]).as_bytes());
+                }
+                '%' if chars.peek() == Some(&'%') => {
+                    chars.next();
+                    literal.push(b'%');
+                }
+                '%' => {
+                    let left_align = chars.next_if_eq(&'-').is_some();
+                    let mut digits = String::new();
+                    while let Some(digit) = chars.next_if(char::is_ascii_digit) {
+                        digits.push(digit);
+                    }
+                    let width = match digits.as_str() {
+                        "" => 0,
+                        digits => digits
+                            .parse()
+                            .ok()
+                            .filter(|width| *width <= MAX_WIDTH)
+                            .ok_or_else(|| format!("invalid width '{}'", digits))?,
+                    };
+                    let directive = match chars.next() {
+                        Some(c) if "pfhsmugtyd".contains(c) => c,
+                        Some(c) => return Err(format!("unknown directive '%{}'", c)),
+                        None => return Err("trailing '%'".to_string()),
+                    };
+                    if !literal.is_empty() {
+                        pieces.push(Piece::Literal(std::mem::take(&mut literal)));
+                    }
+                    pieces.push(Piece::Directive {
+                        directive,
+                        width,
+                        left_align,
+                    });
+                }
+                c => literal.extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes()),
+            }
+        }
+        if !literal.is_empty() {
+            pieces.push(Piece::Literal(literal));
+        }
+        Ok(Format(pieces))
+    }
+}
+
+impl Format {
+    /// The bytes to print for `entry`
+    pub fn render(&self, entry: &Entry) -> Vec<u8> {
+        let mut out = vec![];
+        for piece in &self.0 {
+            match piece {
+                Piece::Literal(bytes) => out.extend_from_slice(bytes),
+                Piece::Directive {
+                    directive,
+                    width,
+                    left_align,
+                } => {
+                    let value = expand(*directive, entry);
+                    let padding = vec![b' '; width.saturating_sub(value.len())];
+                    if *left_align {
+                        out.extend_from_slice(&value);
+                        out.extend(padding);
+                    } else {
+                        out.extend(padding);
+                        out.extend_from_slice(&value);
+                    }
+                }
+            }
+        }
+        out
+    }
+}
+
+/// The value of a `%` directive for `entry`, or nothing if the metadata it
+/// needs is unavailable
+fn expand<'a>(directive: char, entry: &'a Entry) -> Cow<'a, [u8]> {
+    let path = entry.path();
+    let metadata = entry.metadata();
+    match directive {
+        'p' => os_bytes(path.as_os_str()),
+        'f' => os_bytes(entry.file_name()),
+        'h' => match path.parent() {
+            Some(parent) if parent != Path::new("") => os_bytes(parent.as_os_str()),
+            _ => Cow::Borrowed(b"."),
+        },
+        'd' => entry.depth().to_string().into_bytes().into(),
+        'y' => vec![type_char(entry.file_type()) as u8].into(),
+        _ => match metadata {
+            None => Cow::Borrowed(b""),
+            Some(md) => match directive {
+                's' => md.len().to_string().into_bytes().into(),
+                't' => md
+                    .modified()
+                    .map(|time| {
+                        let time: DateTime<Local> = time.into();
+                        time.format("%a %b %e %T %Y").to_string().into_bytes()
+                    })
+                    .unwrap_or_default()
+                    .into(),
+                #[cfg(unix)]
+                'm' => {
+                    use std::os::unix::fs::MetadataExt;
+                    format!("{:o}", md.mode() & 0o7777).into_bytes().into()
+                }
+                #[cfg(unix)]
+                'u' => {
+                    use std::os::unix::fs::MetadataExt;
+                    match users::get_user_by_uid(md.uid()) {
+                        Some(user) => os_bytes(user.name()).into_owned().into(),
+                        None => md.uid().to_string().into_bytes().into(),
+                    }
+                }
+                #[cfg(unix)]
+                'g' => {
+                    use std::os::unix::fs::MetadataExt;
+                    match users::get_group_by_gid(md.gid()) {
+                        Some(group) => os_bytes(group.name()).into_owned().into(),
+                        None => md.gid().to_string().into_bytes().into(),
+                    }
+                }
+                _ => Cow::Borrowed(b"?"),
+            },
+        },
+    }
+}
+
+/// The single-letter type of an entry, as used by `%y`
+pub fn type_char(file_type: FileType) -> char {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::FileTypeExt;
+        if file_type.is_fifo() {
+            return 'p';
+        } else if file_type.is_socket() {
+            return 's';
+        } else if file_type.is_block_device() {
+            return 'b';
+        } else if file_type.is_char_device() {
+            return 'c';
+        }
+    }
+    if file_type.is_symlink() {
+        'l'
+    } else if file_type.is_dir() {
+        'd'
+    } else if file_type.is_file() {
+        'f'
+    } else {
+        'U'
+    }
+}
+
+/// The raw bytes of an OS string on Unix, where paths need not be UTF-8
+#[cfg(unix)]
+pub fn os_bytes(s: &OsStr) -> Cow<'_, [u8]> {
+    use std::os::unix::ffi::OsStrExt;
+    Cow::Borrowed(s.as_bytes())
+}
+
+/// The bytes of an OS string, which is lossy for ill-formed names
+#[cfg(not(unix))]
+pub fn os_bytes(s: &OsStr) -> Cow<'_, [u8]> {
+    match s.to_string_lossy() {
+        Cow::Borrowed(s) => Cow::Borrowed(s.as_bytes()),
+        Cow::Owned(s) => Cow::Owned(s.into_bytes()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Format, Piece};
+
+    #[test]
+    fn test_parse_format() {
+        let format: Format = r"%p\t%-5s|%3d%%\n".parse().unwrap();
+        assert_eq!(
+            format.0,
+            [
+                Piece::Directive {
+                    directive: 'p',
+                    width: 0,
+                    left_align: false
+                },
+                Piece::Literal(b"\t".to_vec()),
+                Piece::Directive {
+                    directive: 's',
+                    width: 5,
+                    left_align: true
+                },
+                Piece::Literal(b"|".to_vec()),
+                Piece::Directive {
+                    directive: 'd',
+                    width: 3,
+                    left_align: false
+                },
+                Piece::Literal(b"%\n".to_vec()),
+            ]
+        );
+        assert!("%q".parse::<Format>().is_err());
+        assert!("%".parse::<Format>().is_err());
+        assert!(r"\q".parse::<Format>().is_err());
+        assert!(r"\".parse::<Format>().is_err());
+        assert!("%4096s".parse::<Format>().is_ok());
+        assert!("%4097s".parse::<Format>().is_err());
+    }
+}
@@ -1,7 +1,19 @@
+// The baseline tests are kept as they were written, before these lints
+#![allow(
+    clippy::needless_borrows_for_generic_args,
+    mismatched_lifetime_syntaxes
+)]
+
 use assert_cmd::Command;
 use predicates::prelude::*;
 use rand::{distributions::Alphanumeric, Rng};
-use std::{borrow::Cow, fs, path::Path};
+use std::{
+    borrow::Cow,
+    fs::{self, File},
+    path::Path,
+    time::{Duration, SystemTime},
+};
+use tempfile::TempDir;
 
 type TestResult = Result<(), Box<dyn std::error::Error>>;
 
@@ -281,6 +293,1116 @@ fn path_g() -> TestResult {
     run(&["tests/inputs/g.csv"], "tests/expected/path_g.txt")
 }
 
+// --------------------------------------------------
+#[test]
+fn max_depth_0() -> TestResult {
+    run(
+        &["tests/inputs", "--max-depth", "0"],
+        "tests/expected/max_depth_0.txt",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn max_depth_1() -> TestResult {
+    run(
+        &["tests/inputs", "--max-depth", "1"],
+        "tests/expected/max_depth_1.txt",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn min_depth_2() -> TestResult {
+    run(
+        &["tests/inputs", "--min-depth", "2"],
+        "tests/expected/min_depth_2.txt",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn min_depth_1_max_depth_2() -> TestResult {
+    run(
+        &["tests/inputs", "--min-depth", "1", "--max-depth", "2"],
+        "tests/expected/min_depth_1_max_depth_2.txt",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn type_f_max_depth_2() -> TestResult {
+    run(
+        &["tests/inputs", "-t", "f", "--max-depth", "2"],
+        "tests/expected/type_f_max_depth_2.txt",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_bad_max_depth() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["--max-depth", "x"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "invalid value 'x' for '--max-depth <DEPTH>'",
+        ));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn expr_name_or_type() -> TestResult {
+    run(
+        &["tests/inputs", "-name", "a*", "-o", "-type", "l"],
+        "tests/expected/expr_name_or_type.txt",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn expr_not_type() -> TestResult {
+    run(
+        &["tests/inputs", "!", "-type", "d"],
+        "tests/expected/expr_not_type.txt",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn expr_parens() -> TestResult {
+    run(
+        &[
+            "tests/inputs",
+            "(",
+            "-name",
+            "*.csv",
+            "-o",
+            "-name",
+            "*.mp3",
+            ")",
+            "-not",
+            "-type",
+            "l",
+        ],
+        "tests/expected/expr_parens.txt",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn expr_type_f_name_or() -> TestResult {
+    run(
+        &[
+            "tests/inputs",
+            "-t",
+            "f",
+            "-name",
+            "a*",
+            "-or",
+            "-name",
+            "*.csv",
+        ],
+        "tests/expected/expr_type_f_name_or.txt",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn name_glob() -> TestResult {
+    run(
+        &["tests/inputs", "-name", "*.csv"],
+        "tests/expected/name_csv.txt",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn iname_glob() -> TestResult {
+    run(
+        &["tests/inputs", "-iname", "*.CSV"],
+        "tests/expected/name_csv.txt",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn name_glob_class() -> TestResult {
+    run(
+        &["tests/inputs", "-name", "[ab].*"],
+        "tests/expected/name_glob_class.txt",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn name_glob_any() -> TestResult {
+    run(
+        &["tests/inputs", "-name", "?.mp3"],
+        "tests/expected/name_glob_any.txt",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn path_glob() -> TestResult {
+    run(
+        &["tests/inputs", "-path", "*/b/*"],
+        "tests/expected/path_glob.txt",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn ipath_glob() -> TestResult {
+    run(
+        &["tests/inputs", "-ipath", "*/A/B"],
+        "tests/expected/ipath_glob.txt",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+#[cfg(not(windows))]
+fn regex_path() -> TestResult {
+    run(
+        &["tests/inputs", "-regex", ".*/[ab]\\.[a-z]+"],
+        "tests/expected/regex_path.txt",
+    )?;
+    run(
+        &["tests/inputs", "-iregex", ".*/A/B/C/C.MP3"],
+        "tests/expected/iregex_path.txt",
+    )?;
+    // The whole path has to match
+    Command::cargo_bin(PRG)?
+        .args(["tests/inputs", "-regex", "b.csv", "-o", "-regex", "tests"])
+        .assert()
+        .success()
+        .stdout("");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn name_regex() -> TestResult {
+    run(
+        &["tests/inputs", "-name-regex", "a"],
+        "tests/expected/name_a.txt",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn prune() -> TestResult {
+    run(
+        &["tests/inputs", "-name", "b", "-prune", "-o", "-print"],
+        "tests/expected/prune_b.txt",
+    )?;
+    run(
+        &["tests/inputs", "-name", "b", "-prune"],
+        "tests/expected/prune_b_only.txt",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn exclude() -> TestResult {
+    run(
+        &["tests/inputs", "--exclude", "a", "--exclude", "*.mp3"],
+        "tests/expected/exclude_a_mp3.txt",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn exclude_min_depth() -> TestResult {
+    run(
+        &["tests/inputs", "--min-depth", "2", "--exclude", "a"],
+        "tests/expected/exclude_min_depth.txt",
+    )
+}
+
+// --------------------------------------------------
+fn dies_expr(args: &[&str], expected: &str) -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(args)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(expected));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_bad_expr() -> TestResult {
+    dies_expr(&["-o", "-name", "a"], "expected an expression before '-o'")?;
+    dies_expr(
+        &["-name", "a", "-and"],
+        "expected an expression after '-and'",
+    )?;
+    dies_expr(&["(", "-name", "a"], "missing ')' in expression")?;
+    dies_expr(&["!"], "expected an expression after '!'")?;
+    dies_expr(&["-name"], "missing argument to '-name'")?;
+    dies_expr(&["-type", "x"], "invalid value 'x' for '-type'")?;
+    dies_expr(&["-name", "[a"], "invalid value '[a' for '-name'")?;
+    dies_expr(&["-regex", "("], "invalid value '(' for '-regex'")
+}
+
+// --------------------------------------------------
+fn run_in(dir: &Path, args: &[&str], expected: &[&str]) -> TestResult {
+    let cmd = Command::cargo_bin(PRG)?
+        .current_dir(dir)
+        .args(args)
+        .assert()
+        .success();
+    let out = cmd.get_output();
+    let stdout = String::from_utf8(out.stdout.clone())?;
+    let mut lines: Vec<&str> = stdout.split('\n').filter(|s| !s.is_empty()).collect();
+    lines.sort();
+    assert_eq!(lines, expected);
+    Ok(())
+}
+
+// --------------------------------------------------
+fn sized_files() -> Result<TempDir, Box<dyn std::error::Error>> {
+    let dir = tempfile::tempdir()?;
+    File::create(dir.path().join("empty"))?;
+    fs::write(dir.path().join("small"), "x".repeat(100))?;
+    fs::write(dir.path().join("big"), "x".repeat(2000))?;
+    fs::create_dir(dir.path().join("empty_dir"))?;
+    fs::create_dir(dir.path().join("full_dir"))?;
+    File::create(dir.path().join("full_dir/file"))?;
+    Ok(dir)
+}
+
+// --------------------------------------------------
+#[test]
+fn size() -> TestResult {
+    let dir = sized_files()?;
+    run_in(dir.path(), &["-type", "f", "-size", "+1k"], &["./big"])?;
+    run_in(
+        dir.path(),
+        &["-type", "f", "-size", "-1k"],
+        &["./empty", "./full_dir/file"],
+    )?;
+    run_in(dir.path(), &["-type", "f", "-size", "100c"], &["./small"])?;
+    run_in(dir.path(), &["-type", "f", "-size", "1"], &["./small"])
+}
+
+// --------------------------------------------------
+#[test]
+fn empty() -> TestResult {
+    let dir = sized_files()?;
+    run_in(
+        dir.path(),
+        &["-empty"],
+        &["./empty", "./empty_dir", "./full_dir/file"],
+    )?;
+    run_in(
+        dir.path(),
+        &["-type", "d", "!", "-empty"],
+        &[".", "./full_dir"],
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn modified() -> TestResult {
+    let dir = tempfile::tempdir()?;
+    let now = SystemTime::now();
+    for (name, age) in [("new", 0), ("hour", 60 * 60), ("week", 7 * 24 * 60 * 60)] {
+        File::create(dir.path().join(name))?.set_modified(now - Duration::from_secs(age))?;
+    }
+    run_in(dir.path(), &["-type", "f", "-mtime", "+1"], &["./week"])?;
+    run_in(
+        dir.path(),
+        &["-type", "f", "-mtime", "0"],
+        &["./hour", "./new"],
+    )?;
+    run_in(dir.path(), &["-type", "f", "-mmin", "-5"], &["./new"])?;
+    run_in(
+        dir.path(),
+        &["-type", "f", "-mmin", "+30"],
+        &["./hour", "./week"],
+    )?;
+    run_in(dir.path(), &["-type", "f", "-newer", "hour"], &["./new"])
+}
+
+// --------------------------------------------------
+#[test]
+#[cfg(unix)]
+fn perm() -> TestResult {
+    use std::os::unix::fs::PermissionsExt;
+
+    let dir = tempfile::tempdir()?;
+    for (name, mode) in [("rw", 0o644), ("rwx", 0o755), ("private", 0o600)] {
+        let path = dir.path().join(name);
+        File::create(&path)?;
+        fs::set_permissions(&path, fs::Permissions::from_mode(mode))?;
+    }
+    run_in(dir.path(), &["-type", "f", "-perm", "644"], &["./rw"])?;
+    run_in(dir.path(), &["-type", "f", "-perm", "u=rw,go=r"], &["./rw"])?;
+    run_in(dir.path(), &["-type", "f", "-perm", "-u+x"], &["./rwx"])?;
+    run_in(
+        dir.path(),
+        &["-type", "f", "-perm", "/o+r"],
+        &["./rw", "./rwx"],
+    )
+}
+
+// --------------------------------------------------
+#[test]
+#[cfg(unix)]
+fn user_group() -> TestResult {
+    use std::os::unix::fs::MetadataExt;
+
+    let dir = tempfile::tempdir()?;
+    File::create(dir.path().join("file"))?;
+    let metadata = fs::metadata(dir.path())?;
+    let uid = metadata.uid().to_string();
+    let gid = metadata.gid().to_string();
+    run_in(dir.path(), &["-type", "f", "-user", &uid], &["./file"])?;
+    run_in(dir.path(), &["-type", "f", "-group", &gid], &["./file"])?;
+    run_in(
+        dir.path(),
+        &["!", "-user", &uid, "-o", "!", "-group", &gid],
+        &[],
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_bad_predicate_value() -> TestResult {
+    dies_expr(&["-size", "1q"], "invalid value '1q' for '-size'")?;
+    dies_expr(&["-mtime", "x"], "invalid value 'x' for '-mtime'")?;
+    dies_expr(&["-mmin", "+-1"], "invalid value '+-1' for '-mmin'")?;
+    dies_expr(&["-newer", &gen_bad_file()], "(os error 2)")
+}
+
+// --------------------------------------------------
+#[test]
+#[cfg(unix)]
+fn dies_bad_perm_user() -> TestResult {
+    dies_expr(&["-perm", "u+q"], "invalid value 'u+q' for '-perm'")?;
+    dies_expr(&["-user", "no-such-user-xyz"], "no such user")
+}
+
+// --------------------------------------------------
+fn exec_files() -> Result<TempDir, Box<dyn std::error::Error>> {
+    let dir = tempfile::tempdir()?;
+    File::create(dir.path().join("empty"))?;
+    fs::write(dir.path().join("full"), "x")?;
+    fs::write(dir.path().join("with space"), "x")?;
+    Ok(dir)
+}
+
+// --------------------------------------------------
+#[test]
+#[cfg(unix)]
+fn exec_each() -> TestResult {
+    let dir = exec_files()?;
+    run_in(
+        dir.path(),
+        &["-type", "f", "-exec", "echo", "<{}>", "{}", ";"],
+        &[
+            "<./empty> ./empty",
+            "<./full> ./full",
+            "<./with space> ./with space",
+        ],
+    )
+}
+
+// --------------------------------------------------
+#[test]
+#[cfg(unix)]
+fn exec_each_is_a_test() -> TestResult {
+    let dir = exec_files()?;
+    // The empty file fails the test, which also sets the exit status
+    let cmd = Command::cargo_bin(PRG)?
+        .current_dir(dir.path())
+        .args(["-type", "f", "-exec", "test", "-s", "{}", ";", "-print"])
+        .assert()
+        .failure();
+    let stdout = String::from_utf8(cmd.get_output().stdout.clone())?;
+    let mut lines: Vec<&str> = stdout.lines().collect();
+    lines.sort();
+    assert_eq!(lines, ["./full", "./with space"]);
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+#[cfg(unix)]
+fn exec_batch() -> TestResult {
+    let dir = exec_files()?;
+    let cmd = Command::cargo_bin(PRG)?
+        .current_dir(dir.path())
+        .args(["-type", "f", "-exec", "echo", "files:", "{}", "+"])
+        .assert()
+        .success();
+    let stdout = String::from_utf8(cmd.get_output().stdout.clone())?;
+    let lines: Vec<&str> = stdout.lines().collect();
+    assert_eq!(lines.len(), 1);
+    let mut files: Vec<&str> = lines[0]
+        .strip_prefix("files: ./")
+        .unwrap()
+        .split(" ./")
+        .collect();
+    files.sort();
+    assert_eq!(files, ["empty", "full", "with space"]);
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+#[cfg(unix)]
+fn exec_failures() -> TestResult {
+    let dir = exec_files()?;
+    // A failed command makes -exec ; false and sets the exit status
+    Command::cargo_bin(PRG)?
+        .current_dir(dir.path())
+        .args(["-type", "f", "-exec", "false", ";", "-print"])
+        .assert()
+        .failure()
+        .stdout("");
+    // A failed batch sets the exit status
+    Command::cargo_bin(PRG)?
+        .current_dir(dir.path())
+        .args(["-type", "f", "-exec", "false", "{}", "+"])
+        .assert()
+        .failure();
+    // So does a command that cannot be run
+    let bad = gen_bad_file();
+    Command::cargo_bin(PRG)?
+        .current_dir(dir.path())
+        .args(["-type", "f", "-exec", &bad, "{}", ";"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(format!("{}: ", bad)));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+#[cfg(unix)]
+fn ok() -> TestResult {
+    let dir = exec_files()?;
+    Command::cargo_bin(PRG)?
+        .current_dir(dir.path())
+        .args(["-name", "full", "-ok", "echo", "yes", "{}", ";"])
+        .write_stdin("y\n")
+        .assert()
+        .success()
+        .stdout("yes ./full\n")
+        .stderr("< echo yes ./full > ? ");
+    Command::cargo_bin(PRG)?
+        .current_dir(dir.path())
+        .args([
+            "-name", "full", "-ok", "echo", "yes", "{}", ";", "-o", "-print",
+        ])
+        .write_stdin("n\n")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("./full\n"))
+        .stdout(predicate::str::contains("yes").not());
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_bad_exec() -> TestResult {
+    dies_expr(&["-exec", "echo", "{}"], "missing argument to '-exec'")?;
+    dies_expr(&["-exec", ";"], "missing argument to '-exec'")?;
+    dies_expr(&["-exec", "{}", "+"], "missing argument to '-exec'")?;
+    dies_expr(&["-ok", "echo", "{}", "+"], "missing argument to '-ok'")
+}
+
+// --------------------------------------------------
+#[test]
+fn print0() -> TestResult {
+    let expected = fs::read_to_string(format_file_name("tests/expected/path_a.txt").as_ref())?;
+    let mut expected: Vec<&str> = expected.lines().collect();
+    expected.sort();
+
+    let cmd = Command::cargo_bin(PRG)?
+        .args(["tests/inputs/a", "-print0"])
+        .assert()
+        .success();
+    let stdout = String::from_utf8(cmd.get_output().stdout.clone())?;
+    assert!(stdout.ends_with('\0'));
+    let mut paths: Vec<&str> = stdout.split_terminator('\0').collect();
+    paths.sort();
+    assert_eq!(paths, expected);
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+#[cfg(target_os = "linux")]
+fn print_raw_names() -> TestResult {
+    use std::{ffi::OsStr, os::unix::ffi::OsStrExt};
+
+    let dir = tempfile::tempdir()?;
+    File::create(dir.path().join("new\nline"))?;
+    File::create(dir.path().join(OsStr::from_bytes(b"bad\xff")))?;
+    for (args, sep) in [
+        (["-type", "f", "-print0"], b'\0'),
+        (["-type", "f", "-print"], b'\n'),
+    ] {
+        let cmd = Command::cargo_bin(PRG)?
+            .current_dir(dir.path())
+            .args(args)
+            .assert()
+            .success();
+        let stdout = &cmd.get_output().stdout;
+        let has = |name: &[u8]| {
+            let mut expected = name.to_vec();
+            expected.push(sep);
+            stdout.windows(expected.len()).any(|w| w == expected)
+        };
+        assert!(has(b"./new\nline"));
+        assert!(has(b"./bad\xff"));
+    }
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn printf() -> TestResult {
+    let dir = tempfile::tempdir()?;
+    fs::create_dir(dir.path().join("dir"))?;
+    fs::write(dir.path().join("dir/file"), "abc")?;
+    run_in(
+        dir.path(),
+        &["-name", "file", "-printf", "%y %d %s|%5s|%-5s|%f %h %p\\n"],
+        &["f 2 3|    3|3    |file ./dir ./dir/file"],
+    )?;
+    run_in(
+        dir.path(),
+        &["-name", "dir", "-printf", "%y:%f:%h\\t%%\\n"],
+        &["d:dir:.\t%"],
+    )
+}
+
+// --------------------------------------------------
+#[test]
+#[cfg(unix)]
+fn printf_owner_mode() -> TestResult {
+    use std::os::unix::fs::{MetadataExt, PermissionsExt};
+
+    let dir = tempfile::tempdir()?;
+    let file = dir.path().join("file");
+    File::create(&file)?;
+    fs::set_permissions(&file, fs::Permissions::from_mode(0o640))?;
+    let metadata = fs::metadata(&file)?;
+    let cmd = Command::cargo_bin(PRG)?
+        .current_dir(dir.path())
+        .args(["-type", "f", "-printf", "%m %u %g\\n"])
+        .assert()
+        .success();
+    let stdout = String::from_utf8(cmd.get_output().stdout.clone())?;
+    let fields: Vec<&str> = stdout.split_whitespace().collect();
+    assert_eq!(fields.len(), 3);
+    assert_eq!(fields[0], "640");
+    // Owners are printed by name, or by ID if they have none
+    let is_owner = |field: &str, id: u32| {
+        field == id.to_string() || !field.chars().all(|c| c.is_ascii_digit())
+    };
+    assert!(is_owner(fields[1], metadata.uid()));
+    assert!(is_owner(fields[2], metadata.gid()));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_bad_printf() -> TestResult {
+    dies_expr(&["-printf", "%q"], "unknown directive '%q'")?;
+    dies_expr(&["-printf", "%"], "trailing '%'")?;
+    dies_expr(&["-printf", "\\q"], "unknown escape '\\q'")?;
+    dies_expr(
+        &["-printf", "%1000000000000s"],
+        "invalid width '1000000000000'",
+    )?;
+    dies_expr(
+        &["-printf", "%99999999999999999999999s"],
+        "invalid width '99999999999999999999999'",
+    )
+}
+
+// --------------------------------------------------
+fn git_repo() -> Result<TempDir, Box<dyn std::error::Error>> {
+    let dir = tempfile::tempdir()?;
+    let repo = dir.path().join("repo");
+    for subdir in [".git/info", "target/debug", "src/gen", "logs"] {
+        fs::create_dir_all(repo.join(subdir))?;
+    }
+    fs::write(repo.join(".gitignore"), "target/\n*.log\n!keep.log\n")?;
+    fs::write(repo.join(".git/info/exclude"), "secret\n")?;
+    fs::write(repo.join("src/.ignore"), "gen\n")?;
+    for file in [
+        "target/debug/app",
+        "src/main.rs",
+        "src/gen/out.rs",
+        "logs/debug.log",
+        "logs/keep.log",
+        "secret",
+        "notes.txt",
+    ] {
+        File::create(repo.join(file))?;
+    }
+    Ok(dir)
+}
+
+// --------------------------------------------------
+fn run_gitignore(dir: &Path, args: &[&str], expected: &[&str]) -> TestResult {
+    // Keep the global excludes file of the user out of the way
+    let cmd = Command::cargo_bin(PRG)?
+        .current_dir(dir)
+        .env("HOME", dir)
+        .env("XDG_CONFIG_HOME", dir.join(".config"))
+        .arg("--gitignore")
+        .args(args)
+        .assert()
+        .success();
+    let stdout = String::from_utf8(cmd.get_output().stdout.clone())?;
+    let mut lines: Vec<&str> = stdout.lines().collect();
+    lines.sort();
+    assert_eq!(lines, expected);
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn gitignore() -> TestResult {
+    let dir = git_repo()?;
+    run_gitignore(
+        dir.path(),
+        &["repo", "-type", "f"],
+        &[
+            "repo/.gitignore",
+            "repo/logs/keep.log",
+            "repo/notes.txt",
+            "repo/src/.ignore",
+            "repo/src/main.rs",
+        ],
+    )?;
+    // Ignore files above the search path apply too
+    run_gitignore(&dir.path().join("repo/logs"), &[], &[".", "./keep.log"])
+}
+
+// --------------------------------------------------
+#[test]
+fn gitignore_outside_repo() -> TestResult {
+    let dir = git_repo()?;
+    fs::remove_dir_all(dir.path().join("repo/.git"))?;
+    run_gitignore(
+        dir.path(),
+        &["repo", "-type", "f"],
+        &[
+            "repo/.gitignore",
+            "repo/logs/debug.log",
+            "repo/logs/keep.log",
+            "repo/notes.txt",
+            "repo/secret",
+            "repo/src/.ignore",
+            "repo/src/main.rs",
+            "repo/target/debug/app",
+        ],
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn gitignore_global() -> TestResult {
+    let dir = git_repo()?;
+    fs::create_dir_all(dir.path().join(".config/git"))?;
+    fs::write(dir.path().join(".config/git/ignore"), "*.txt\n")?;
+    run_gitignore(
+        dir.path(),
+        &["repo", "-type", "f"],
+        &[
+            "repo/.gitignore",
+            "repo/logs/keep.log",
+            "repo/src/.ignore",
+            "repo/src/main.rs",
+        ],
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn gitignore_exclude_overrides() -> TestResult {
+    let dir = git_repo()?;
+    run_gitignore(
+        dir.path(),
+        &[
+            "repo",
+            "-type",
+            "f",
+            "--exclude",
+            "src",
+            "--exclude",
+            "!*.log",
+        ],
+        &[
+            "repo/.gitignore",
+            "repo/logs/debug.log",
+            "repo/logs/keep.log",
+            "repo/notes.txt",
+        ],
+    )
+}
+
+// --------------------------------------------------
+fn run_links(args: &[&str], expected: &[&str], errors: &[&str]) -> TestResult {
+    let cmd = Command::cargo_bin(PRG)?.args(args).assert().success();
+    let out = cmd.get_output();
+    let stdout = String::from_utf8(out.stdout.clone())?;
+    let mut lines: Vec<&str> = stdout.lines().collect();
+    lines.sort();
+    assert_eq!(lines, expected);
+    let stderr = String::from_utf8(out.stderr.clone())?;
+    let mut lines: Vec<&str> = stderr.lines().collect();
+    lines.sort();
+    assert_eq!(lines, errors);
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+#[cfg(unix)]
+fn links_never_followed() -> TestResult {
+    for mode in [&[][..], &["-P"], &["-L", "-P"]] {
+        let args = [mode, &["tests/links", "-type", "l"]].concat();
+        run_links(
+            &args,
+            &[
+                "tests/links/broken",
+                "tests/links/dir/loop",
+                "tests/links/to_dir",
+                "tests/links/to_file",
+            ],
+            &[],
+        )?;
+        let args = [mode, &["tests/links", "-type", "d"]].concat();
+        run_links(&args, &["tests/links", "tests/links/dir"], &[])?;
+    }
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+#[cfg(unix)]
+fn links_followed() -> TestResult {
+    let loops = [
+        "File system loop found: tests/links/dir/loop points to an ancestor tests/links",
+        "File system loop found: tests/links/to_dir/loop points to an ancestor tests/links",
+    ];
+    // Only broken links are still links
+    run_links(
+        &["-L", "tests/links", "-type", "l"],
+        &["tests/links/broken"],
+        &loops,
+    )?;
+    run_links(
+        &["-L", "tests/links", "-type", "f"],
+        &[
+            "tests/links/dir/file",
+            "tests/links/to_dir/file",
+            "tests/links/to_file",
+        ],
+        &loops,
+    )?;
+    run_links(
+        &["-L", "tests/links", "-type", "d"],
+        &["tests/links", "tests/links/dir", "tests/links/to_dir"],
+        &loops,
+    )?;
+    // Tests on metadata see the target
+    run_links(
+        &["-L", "tests/links", "-name", "to_*", "-size", "2c"],
+        &["tests/links/to_file"],
+        &loops,
+    )
+}
+
+// --------------------------------------------------
+#[test]
+#[cfg(unix)]
+fn links_followed_on_command_line() -> TestResult {
+    run_links(&["tests/links/to_dir"], &["tests/links/to_dir"], &[])?;
+    run_links(
+        &["-H", "tests/links/to_dir"],
+        &[
+            "tests/links/to_dir",
+            "tests/links/to_dir/file",
+            "tests/links/to_dir/loop",
+        ],
+        &[],
+    )?;
+    run_links(
+        &["-H", "tests/links/to_dir", "-type", "l"],
+        &["tests/links/to_dir/loop"],
+        &[],
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn xdev() -> TestResult {
+    run(&["tests/inputs", "-xdev"], "tests/expected/path1.txt")?;
+    run(&["--xdev", "tests/inputs"], "tests/expected/path1.txt")
+}
+
+// --------------------------------------------------
+#[cfg(unix)]
+fn special_files() -> Result<TempDir, Box<dyn std::error::Error>> {
+    use std::os::unix::{fs::symlink, net::UnixListener};
+    let dir = tempfile::tempdir()?;
+    File::create(dir.path().join("file"))?;
+    fs::create_dir(dir.path().join("dir"))?;
+    let status = std::process::Command::new("mkfifo")
+        .arg(dir.path().join("fifo"))
+        .status()?;
+    assert!(status.success());
+    UnixListener::bind(dir.path().join("socket"))?;
+    symlink("fifo", dir.path().join("to_fifo"))?;
+    symlink("dir", dir.path().join("to_dir"))?;
+    symlink("nowhere", dir.path().join("broken"))?;
+    Ok(dir)
+}
+
+// --------------------------------------------------
+#[test]
+#[cfg(unix)]
+fn type_special() -> TestResult {
+    let dir = special_files()?;
+    run_in(dir.path(), &["-type", "p"], &["./fifo"])?;
+    run_in(dir.path(), &["-type", "s"], &["./socket"])?;
+    run_in(dir.path(), &["-type", "p,s"], &["./fifo", "./socket"])?;
+    run_in(dir.path(), &["-type", "f,d"], &[".", "./dir", "./file"])?;
+    run_in(dir.path(), &["-t", "s,f"], &["./file", "./socket"])?;
+    run_in(dir.path(), &["-L", "-type", "p"], &["./fifo", "./to_fifo"])?;
+    run_in(dir.path(), &["/dev/null", "-type", "c"], &["/dev/null"])?;
+    run_in(dir.path(), &["/dev/null", "-type", "f,b"], &[])
+}
+
+// --------------------------------------------------
+#[test]
+#[cfg(unix)]
+fn xtype() -> TestResult {
+    let dir = special_files()?;
+    run_in(dir.path(), &["-xtype", "p"], &["./fifo", "./to_fifo"])?;
+    run_in(dir.path(), &["-xtype", "l"], &["./broken"])?;
+    run_in(dir.path(), &["-xtype", "d", "-type", "l"], &["./to_dir"])?;
+    run_in(
+        dir.path(),
+        &["-L", "-xtype", "l"],
+        &["./broken", "./to_dir", "./to_fifo"],
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_bad_type_list() -> TestResult {
+    dies_expr(&["-type", "f,x"], "invalid value 'f,x' for '-type'")?;
+    dies_expr(&["-xtype", "f,"], "invalid value 'f,' for '-xtype'")
+}
+
+// --------------------------------------------------
+fn delete_files() -> Result<TempDir, Box<dyn std::error::Error>> {
+    let dir = tempfile::tempdir()?;
+    File::create(dir.path().join("a.tmp"))?;
+    File::create(dir.path().join("b.txt"))?;
+    fs::create_dir(dir.path().join("sub"))?;
+    File::create(dir.path().join("sub/c.tmp"))?;
+    File::create(dir.path().join("sub/d.txt"))?;
+    fs::create_dir(dir.path().join("empty"))?;
+    Ok(dir)
+}
+
+// --------------------------------------------------
+fn remaining(dir: &Path) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+    let mut paths = vec![];
+    for entry in walkdir::WalkDir::new(dir).min_depth(1) {
+        let path = entry?.path().strip_prefix(dir)?.to_path_buf();
+        paths.push(path.to_string_lossy().replace('\\', "/"));
+    }
+    paths.sort();
+    Ok(paths)
+}
+
+// --------------------------------------------------
+#[test]
+fn delete() -> TestResult {
+    let dir = delete_files()?;
+    run_in(dir.path(), &["-name", "*.tmp", "-delete"], &[])?;
+    assert_eq!(
+        remaining(dir.path())?,
+        ["b.txt", "empty", "sub", "sub/d.txt"]
+    );
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn delete_depth_first() -> TestResult {
+    let dir = delete_files()?;
+    run_in(dir.path(), &["sub", "--force", "-delete"], &[])?;
+    assert_eq!(remaining(dir.path())?, ["a.tmp", "b.txt", "empty"]);
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn delete_current_dir() -> TestResult {
+    let dir = delete_files()?;
+    run_in(dir.path(), &["--force", "-delete"], &[])?;
+    assert!(dir.path().exists());
+    assert_eq!(remaining(dir.path())?, Vec::<String>::new());
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn delete_dry_run() -> TestResult {
+    let dir = delete_files()?;
+    let cmd = Command::cargo_bin(PRG)?
+        .current_dir(dir.path())
+        .args(["sub", "--dry-run", "--force", "-delete"])
+        .assert()
+        .success();
+    let stdout = String::from_utf8(cmd.get_output().stdout.clone())?;
+    let lines: Vec<&str> = stdout.lines().collect();
+    assert_eq!(lines.len(), 3);
+    assert_eq!(lines.last(), Some(&"sub"));
+    assert_eq!(remaining(dir.path())?.len(), 6);
+
+    run_in(
+        dir.path(),
+        &["--dry-run", "-name", "*.tmp", "-delete"],
+        &["./a.tmp", "./sub/c.tmp"],
+    )?;
+    assert_eq!(remaining(dir.path())?.len(), 6);
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn delete_reports_errors() -> TestResult {
+    let dir = delete_files()?;
+    Command::cargo_bin(PRG)?
+        .current_dir(dir.path())
+        .args(["--min-depth", "1", "-type", "d", "-delete"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("sub: "));
+    assert_eq!(
+        remaining(dir.path())?,
+        ["a.tmp", "b.txt", "sub", "sub/c.tmp", "sub/d.txt"]
+    );
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_delete_without_test() -> TestResult {
+    let dir = delete_files()?;
+    for args in [&["-delete"][..], &["--dry-run", "-delete", "-print"]] {
+        Command::cargo_bin(PRG)?
+            .current_dir(dir.path())
+            .args(args)
+            .assert()
+            .failure()
+            .stderr(predicate::str::contains("use --force"));
+    }
+    Command::cargo_bin(PRG)?
+        .current_dir(dir.path())
+        .args(["-L", "-name", "*.tmp", "-delete"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("'-delete' cannot be used with -L"));
+    assert_eq!(remaining(dir.path())?.len(), 6);
+    Ok(())
+}
+
+// --------------------------------------------------
+fn run_threads(args: &[&str]) -> TestResult {
+    let out = Command::cargo_bin(PRG)?.args(args).arg("--sort").output()?;
+    let parallel = Command::cargo_bin(PRG)?
+        .args(args)
+        .args(["--sort", "--threads", "4"])
+        .output()?;
+    assert_eq!(out.status.code(), parallel.status.code());
+    assert_eq!(
+        String::from_utf8(out.stdout)?,
+        String::from_utf8(parallel.stdout)?
+    );
+    // Errors come in the order the threads find them
+    let stderr = String::from_utf8(out.stderr)?;
+    let parallel_stderr = String::from_utf8(parallel.stderr)?;
+    let mut errors: Vec<&str> = stderr.lines().collect();
+    let mut parallel_errors: Vec<&str> = parallel_stderr.lines().collect();
+    errors.sort();
+    parallel_errors.sort();
+    assert_eq!(errors, parallel_errors);
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn threads() -> TestResult {
+    run_threads(&["tests/inputs"])?;
+    run_threads(&["tests/inputs", "tests/inputs/d", "-type", "f"])?;
+    run_threads(&["tests/inputs", "--max-depth", "1", "--min-depth", "1"])?;
+    run_threads(&["tests/inputs", "-name", "d", "-prune", "-o", "-print"])?;
+    run_threads(&["tests/inputs", "--exclude", "e", "-printf", "%d %p\n"])?;
+    run_threads(&["tests/links"])?;
+    run_threads(&["-H", "tests/links/to_dir"])?;
+    run_threads(&["-L", "tests/links", "-xdev"])?;
+    run_threads(&[&gen_bad_file()])
+}
+
+// --------------------------------------------------
+#[test]
+fn threads_unsorted() -> TestResult {
+    let dir = delete_files()?;
+    run_in(
+        dir.path(),
+        &["--threads", "0", "-type", "f"],
+        &["./a.tmp", "./b.txt", "./sub/c.tmp", "./sub/d.txt"],
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn threads_gitignore() -> TestResult {
+    let dir = git_repo()?;
+    run_gitignore(
+        dir.path(),
+        &["repo", "-type", "f", "--threads", "3"],
+        &[
+            "repo/.gitignore",
+            "repo/logs/keep.log",
+            "repo/notes.txt",
+            "repo/src/.ignore",
+            "repo/src/main.rs",
+        ],
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_threads_delete() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["--threads", "2", "-name", "*.tmp", "-delete"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("cannot be used with --threads"));
+    Ok(())
+}
+
 // --------------------------------------------------
 #[test]
 #[cfg(not(windows))]
@@ -0,0 +1,6 @@
+fn main() {
+    if let Err(e) = cutr::paste::get_args().and_then(cutr::paste::run) {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    }
+}
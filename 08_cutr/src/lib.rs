@@ -1,24 +1,97 @@
+pub mod paste;
+
 use crate::Extract::*;
 use clap::{ArgGroup, Parser};
-use csv::{ReaderBuilder, StringRecord, WriterBuilder};
+use csv::{QuoteStyle, ReaderBuilder, Terminator, WriterBuilder};
+use regex::Regex;
 use std::{
     error::Error,
     fs::File,
-    io::{self, BufRead, BufReader},
-    num::NonZeroUsize,
-    ops::Range,
+    io::{self, BufRead, BufReader, Write},
+    ops::{Range, RangeFrom},
 };
 
 type MyResult<T> = Result<T, Box<dyn Error>>;
-type PositionList = Vec<Range<usize>>;
+type PositionList = Vec<PositionRange>;
+
+/// A range of positions counted from 0 like a `Range`, except that its end
+/// is the end of the line when it is open, as in `3-`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PositionRange {
+    start: usize,
+    end: Option<usize>,
+}
+
+impl PositionRange {
+    /// The positions of the range that exist in a line of `len` positions
+    fn resolve(&self, len: usize) -> Range<usize> {
+        self.start.min(len)..self.end.map_or(len, |end| end.min(len))
+    }
+}
+
+impl From<Range<usize>> for PositionRange {
+    fn from(range: Range<usize>) -> Self {
+        PositionRange {
+            start: range.start,
+            end: Some(range.end),
+        }
+    }
+}
+
+impl From<RangeFrom<usize>> for PositionRange {
+    fn from(range: RangeFrom<usize>) -> Self {
+        PositionRange {
+            start: range.start,
+            end: None,
+        }
+    }
+}
 
 #[derive(Debug)]
 pub enum Extract {
     Fields(PositionList),
+    /// Fields given by position or by column name, which are resolved
+    /// against the header of each file
+    Columns(String),
     Bytes(PositionList),
     Chars(PositionList),
 }
 
+/// How the fields of a line are split
+#[derive(Debug)]
+pub enum Delimiter {
+    /// On every occurrence of a string
+    Str(String),
+    /// On every match of a regular expression
+    Regex(Regex),
+    /// On runs of whitespace, ignoring leading and trailing blanks like awk
+    Whitespace,
+}
+
+impl Delimiter {
+    fn split<'a>(&self, line: &'a str) -> Vec<&'a str> {
+        match self {
+            Delimiter::Str(delim) => line.split(delim.as_str()).collect(),
+            Delimiter::Regex(re) => re.split(line).collect(),
+            Delimiter::Whitespace => line.split_whitespace().collect(),
+        }
+    }
+}
+
+/// How CSV records are read and written
+#[derive(Debug)]
+pub struct Dialect {
+    delimiter: u8,
+    quote: u8,
+    /// The byte escaping quotes, which are doubled when there is none
+    escape: Option<u8>,
+    output_delimiter: u8,
+    output_quote: u8,
+    output_escape: Option<u8>,
+    quote_all: bool,
+    crlf: bool,
+}
+
 #[derive(Debug, Parser)]
 #[command(author, version, about, long_about = None)]
 #[clap(group(
@@ -30,36 +103,199 @@ pub struct Cli {
     #[arg(help = "Input file(s)", value_name = "FILE", default_value = "-")]
     files: Vec<String>,
 
-    #[arg(short, long = "delim", help = "Field delimiter", default_value = "\t", value_parser = parse_delim)]
-    delimiter: u8,
+    #[arg(short, long = "delim", help = "Field delimiter", default_value = "\t")]
+    delimiter: String,
+
+    #[arg(
+        long,
+        help = "Split fields on the matches of a regular expression",
+        value_name = "REGEX",
+        allow_hyphen_values = true,
+        conflicts_with_all = ["delimiter", "bytes", "chars", "csv"]
+    )]
+    regex_delim: Option<Regex>,
+
+    #[arg(
+        short,
+        long,
+        help = "Split fields on runs of whitespace, ignoring leading blanks",
+        conflicts_with_all = ["delimiter", "regex_delim", "bytes", "chars", "csv"]
+    )]
+    whitespace: bool,
 
-    #[arg(short, long, help = "Selected bytes")]
+    #[arg(short, long, help = "Selected bytes", allow_hyphen_values = true)]
     bytes: Option<String>,
 
-    #[arg(short, long, help = "Selected characters")]
+    #[arg(short, long, help = "Selected characters", allow_hyphen_values = true)]
     chars: Option<String>,
 
-    #[arg(short, long, help = "Selected fields")]
+    #[arg(short, long, help = "Selected fields", allow_hyphen_values = true)]
     fields: Option<String>,
+
+    #[arg(
+        long,
+        help = "Select the complement of the bytes, characters or fields"
+    )]
+    complement: bool,
+
+    #[arg(
+        long,
+        help = "Output delimiter [default: the field delimiter for fields, a space for \
+                --regex-delim and --whitespace, none otherwise]",
+        value_name = "STRING"
+    )]
+    output_delimiter: Option<String>,
+
+    #[arg(
+        long,
+        help = "Parse fields as CSV, where delimiters may be quoted",
+        conflicts_with_all = ["bytes", "chars"]
+    )]
+    csv: bool,
+
+    #[arg(
+        short = 's',
+        long,
+        help = "Skip lines without a field delimiter",
+        conflicts_with_all = ["bytes", "chars"]
+    )]
+    only_delimited: bool,
+
+    #[arg(
+        long,
+        help = "Select fields by column name too, and print the first record as a header",
+        requires = "csv",
+        conflicts_with_all = ["bytes", "chars"]
+    )]
+    header: bool,
+
+    #[arg(
+        long,
+        help = "CSV quote character",
+        value_name = "CHAR",
+        default_value = "\"",
+        requires = "csv"
+    )]
+    quote: String,
+
+    #[arg(
+        long,
+        help = "CSV escape character for quotes, which are doubled otherwise",
+        value_name = "CHAR",
+        requires = "csv"
+    )]
+    escape: Option<String>,
+
+    #[arg(
+        long,
+        help = "CSV output quote character [default: the quote character]",
+        value_name = "CHAR",
+        requires = "csv"
+    )]
+    output_quote: Option<String>,
+
+    #[arg(
+        long,
+        help = "CSV output escape character [default: the escape character]",
+        value_name = "CHAR",
+        requires = "csv"
+    )]
+    output_escape: Option<String>,
+
+    #[arg(long, help = "Quote every CSV output field", requires = "csv")]
+    quote_all: bool,
+
+    #[arg(
+        long,
+        help = "Quote CSV output fields only when needed (default)",
+        requires = "csv",
+        conflicts_with = "quote_all"
+    )]
+    quote_minimal: bool,
+
+    #[arg(long, help = "End CSV output records with CRLF", requires = "csv")]
+    crlf: bool,
+
+    #[arg(
+        short = 'n',
+        help = "Do not split multibyte characters when selecting bytes",
+        conflicts_with_all = ["chars", "fields"]
+    )]
+    no_split: bool,
+
+    #[arg(
+        long,
+        help = "Write the selected bytes as they are, even if they are not valid UTF-8",
+        conflicts_with_all = ["chars", "fields"]
+    )]
+    raw: bool,
 }
 
 #[derive(Debug)]
 pub struct Config {
     files: Vec<String>,
-    delimiter: u8,
+    delimiter: Delimiter,
     extract: Extract,
+    complement: bool,
+    output_delimiter: Option<String>,
+    csv: Option<Dialect>,
+    only_delimited: bool,
+    no_split: bool,
+    raw: bool,
 }
 
 pub fn get_args() -> MyResult<Config> {
     let cli = Cli::try_parse().map_err(|e| -> Box<dyn Error> { e.into() })?;
     let files = cli.files;
-    let delimiter = cli.delimiter;
+    if cli.delimiter.is_empty() {
+        return Err("--delim \"\" must not be empty".into());
+    }
+    let csv = if cli.csv {
+        let delimiter = parse_byte("--delim", &cli.delimiter)?;
+        let quote = parse_byte("--quote", &cli.quote)?;
+        let escape = match &cli.escape {
+            Some(escape) => Some(parse_byte("--escape", escape)?),
+            None => None,
+        };
+        Some(Dialect {
+            delimiter,
+            quote,
+            escape,
+            output_delimiter: match &cli.output_delimiter {
+                Some(delim) => parse_byte("--output-delimiter", delim)?,
+                None => delimiter,
+            },
+            output_quote: match &cli.output_quote {
+                Some(quote) => parse_byte("--output-quote", quote)?,
+                None => quote,
+            },
+            output_escape: match &cli.output_escape {
+                Some(escape) => Some(parse_byte("--output-escape", escape)?),
+                None => escape,
+            },
+            quote_all: cli.quote_all,
+            crlf: cli.crlf,
+        })
+    } else {
+        None
+    };
+    let delimiter = match cli.regex_delim {
+        Some(re) => Delimiter::Regex(re),
+        None if cli.whitespace => Delimiter::Whitespace,
+        None => Delimiter::Str(cli.delimiter),
+    };
     let extract = if let Some(list) = cli.bytes {
-        Bytes(parse_pos(&list)?)
+        Bytes(parse_pos(&list, None)?)
     } else if let Some(list) = cli.fields {
-        Fields(parse_pos(&list)?)
+        if cli.header {
+            // Check the positions now, leaving the names for each file
+            parse_pos(&list, Some(&[]))?;
+            Columns(list)
+        } else {
+            Fields(parse_pos(&list, None)?)
+        }
     } else if let Some(list) = cli.chars {
-        Chars(parse_pos(&list)?)
+        Chars(parse_pos(&list, None)?)
     } else {
         unreachable!();
     };
@@ -67,243 +303,557 @@ pub fn get_args() -> MyResult<Config> {
         files,
         delimiter,
         extract,
+        complement: cli.complement,
+        output_delimiter: cli.output_delimiter,
+        csv,
+        only_delimited: cli.only_delimited,
+        no_split: cli.no_split,
+        raw: cli.raw,
     })
 }
 
-fn parse_delim(delimiter: &str) -> Result<u8, String> {
-    let bytes = delimiter.as_bytes();
-    if bytes.len() > 1 {
-        return Err(format!("--delim \"{}\" must be a single byte", delimiter).into());
+/// Parse the value of a CSV option, which must be a single byte
+fn parse_byte(option: &str, value: &str) -> Result<u8, String> {
+    match value.as_bytes() {
+        [byte] => Ok(*byte),
+        _ => Err(format!("{} \"{}\" must be a single byte", option, value)),
     }
-    bytes
-        .get(0)
-        .ok_or(format!("--delim \"{}\" must be a single byte", delimiter))
-        .copied()
 }
 
-fn parse_pos(range: &str) -> MyResult<PositionList> {
+/// Parse a list of positions, or with a `header` of column names also a
+/// list of names, a name being any value other than a number or a range
+///
+/// An empty header only checks the positions in the list.
+fn parse_pos(range: &str, header: Option<&[&str]>) -> MyResult<PositionList> {
     if range.is_empty() {
-        return Err(format!("empty range").into());
+        return Err("empty range".into());
     }
     let value_error = |val: &str| format!("illegal list value: \"{}\"", val);
     range
         .split(',')
         .map(|val| {
+            if let Some(header) = header {
+                if let Some(i) = header.iter().position(|name| *name == val) {
+                    return Ok((i..i + 1).into());
+                }
+                let is_name = !val.is_empty()
+                    && !val
+                        .bytes()
+                        .all(|b| b.is_ascii_digit() || b == b'-' || b == b'+');
+                if is_name && header.is_empty() {
+                    return Ok((0..0).into());
+                } else if is_name {
+                    return Err(format!("unknown field name: \"{}\"", val));
+                }
+            }
             let range: Vec<&str> = val.split('-').collect();
             if range.len() > 2 {
                 return Err(value_error(val));
             }
-            let start = range.get(0).ok_or(value_error(val)).map(|bound| {
-                bound
-                    .starts_with("+")
-                    .then(|| Err(value_error(val)))
-                    .unwrap_or_else(|| {
-                        bound
-                            .parse::<NonZeroUsize>()
-                            .map(|n| usize::from(n) - 1)
-                            .map_err(|_| value_error(bound))
-                    })
-            })??;
-            let end = range
-                .get(1)
-                .map(|bound| {
-                    bound
-                        .starts_with("+")
-                        .then(|| Err(value_error(val)))
-                        .unwrap_or_else(|| {
-                            bound
-                                .parse::<NonZeroUsize>()
-                                .map(|n| usize::from(n))
-                                .map_err(|_| value_error(bound))
-                        })
-                })
-                .unwrap_or_else(|| Ok(start + 1))?;
-            if range.len() == 2 && end <= start {
-                return Err(format!(
-                    "First number in range ({}) must be lower than second number ({})",
-                    start, end
-                ));
+            let parse_bound = |bound: &str| {
+                if bound.starts_with('+') {
+                    return Err(value_error(val));
+                }
+                match bound.parse::<usize>() {
+                    Ok(0) => Err(value_error(bound)),
+                    Ok(n) => Ok(n),
+                    Err(_) => Err(value_error(val)),
+                }
             };
-            Ok(start..end)
+            match (range[0], range.get(1).copied()) {
+                (start, None) => {
+                    let start = parse_bound(start)?;
+                    Ok((start - 1..start).into())
+                }
+                ("", Some("")) => Err(value_error(val)),
+                ("", Some(end)) => Ok((0..parse_bound(end)?).into()),
+                (start, Some("")) => Ok((parse_bound(start)? - 1..).into()),
+                (start, Some(end)) => {
+                    let (start, end) = (parse_bound(start)?, parse_bound(end)?);
+                    if end <= start {
+                        return Err(format!(
+                            "First number in range ({}) must be lower than second number ({})",
+                            start, end
+                        ));
+                    }
+                    Ok((start - 1..end).into())
+                }
+            }
         })
         .collect::<Result<_, _>>()
         .map_err(From::from)
 }
 
+pub fn run(config: Config) -> MyResult<()> {
+    let output_delimiter = config.output_delimiter.as_deref().unwrap_or("");
+    for filename in &config.files {
+        match open(filename) {
+            Err(err) => eprintln!("{}: {}", filename, err),
+            Ok(file) => match &config.extract {
+                Bytes(pos) => {
+                    for line in lines(file) {
+                        let line = line?;
+                        let bytes = extract_bytes(&line, pos, config.complement, config.no_split);
+                        if config.raw {
+                            let mut out = bytes.join(output_delimiter.as_bytes());
+                            out.push(b'\n');
+                            io::stdout().write_all(&out)?;
+                        } else {
+                            let bytes: Vec<_> =
+                                bytes.iter().map(|b| String::from_utf8_lossy(b)).collect();
+                            println!("{}", bytes.join(output_delimiter));
+                        }
+                    }
+                }
+                Chars(pos) => {
+                    for line in lines(file) {
+                        let line = String::from_utf8_lossy(&line?).into_owned();
+                        let chars = extract_chars(&line, pos, config.complement);
+                        println!("{}", chars.join(output_delimiter));
+                    }
+                }
+                Fields(_) | Columns(_) if config.csv.is_some() => cut_csv(file, &config)?,
+                Fields(pos) => cut_fields(file, pos, &config)?,
+                Columns(_) => unreachable!(),
+            },
+        }
+    }
+    Ok(())
+}
+
+/// Cut the fields of each line split on every delimiter, printing the lines
+/// without any delimiter as they are unless `--only-delimited` is set
+fn cut_fields(file: Box<dyn BufRead>, pos: &[PositionRange], config: &Config) -> MyResult<()> {
+    let output_delimiter = match (&config.output_delimiter, &config.delimiter) {
+        (Some(delim), _) | (None, Delimiter::Str(delim)) => delim.as_str(),
+        (None, _) => " ",
+    };
+    for line in lines(file) {
+        let line = String::from_utf8_lossy(&line?).into_owned();
+        let fields = config.delimiter.split(&line);
+        if fields.len() < 2 {
+            if !config.only_delimited {
+                println!("{}", line);
+            }
+            continue;
+        }
+        let fields = extract_fields(&fields, pos, config.complement);
+        println!("{}", fields.join(output_delimiter));
+    }
+    Ok(())
+}
+
+/// Cut the fields of each CSV record, which are quoted as needed on output,
+/// resolving any column names against the first record
+fn cut_csv(file: Box<dyn BufRead>, config: &Config) -> MyResult<()> {
+    let dialect = config.csv.as_ref().expect("CSV dialect");
+    let mut rdr = ReaderBuilder::new()
+        .delimiter(dialect.delimiter)
+        .quote(dialect.quote)
+        .escape(dialect.escape)
+        .double_quote(dialect.escape.is_none())
+        .has_headers(false)
+        .flexible(true)
+        .from_reader(file);
+    let mut wtr = WriterBuilder::new()
+        .delimiter(dialect.output_delimiter)
+        .quote(dialect.output_quote)
+        .double_quote(dialect.output_escape.is_none())
+        .escape(dialect.output_escape.unwrap_or(b'\\'))
+        .quote_style(match dialect.quote_all {
+            true => QuoteStyle::Always,
+            false => QuoteStyle::Necessary,
+        })
+        .terminator(match dialect.crlf {
+            true => Terminator::CRLF,
+            false => Terminator::Any(b'\n'),
+        })
+        .flexible(true)
+        .from_writer(io::stdout());
+    let mut records = rdr.records();
+    let pos = match &config.extract {
+        Fields(pos) => pos.clone(),
+        Columns(list) => match records.next() {
+            None => return Ok(()),
+            Some(header) => {
+                let header = header?;
+                let names: Vec<&str> = header.iter().collect();
+                let pos = parse_pos(list, Some(&names))?;
+                wtr.write_record(extract_fields(&names, &pos, config.complement))?;
+                pos
+            }
+        },
+        _ => unreachable!(),
+    };
+    for record in records {
+        let record = record?;
+        if config.only_delimited && record.len() < 2 {
+            continue;
+        }
+        let fields: Vec<&str> = record.iter().collect();
+        wtr.write_record(extract_fields(&fields, &pos, config.complement))?;
+    }
+    Ok(())
+}
+
+fn open(filename: &str) -> MyResult<Box<dyn BufRead>> {
+    match filename {
+        "-" => Ok(Box::new(BufReader::new(io::stdin()))),
+        _ => Ok(Box::new(BufReader::new(File::open(filename)?))),
+    }
+}
+
+/// The lines of `file` as bytes, which unlike `BufRead::lines` may not be
+/// valid UTF-8, without their line endings
+fn lines(file: Box<dyn BufRead>) -> impl Iterator<Item = io::Result<Vec<u8>>> {
+    file.split(b'\n').map(|line| {
+        line.map(|mut line| {
+            if line.last() == Some(&b'\r') {
+                line.pop();
+            }
+            line
+        })
+    })
+}
+
+/// The ranges to extract from a line of `len` positions: the ranges of
+/// `pos` resolved against the line, in order, or with `complement` the runs
+/// of positions outside all of them
+fn select(pos: &[PositionRange], len: usize, complement: bool) -> Vec<Range<usize>> {
+    if !complement {
+        return pos
+            .iter()
+            .map(|range| range.resolve(len))
+            .filter(|range| !range.is_empty())
+            .collect();
+    }
+    let mut selected = vec![true; len];
+    for range in pos {
+        selected[range.resolve(len)].fill(false);
+    }
+    let mut ranges: Vec<Range<usize>> = vec![];
+    for (i, _) in selected
+        .iter()
+        .enumerate()
+        .filter(|(_, selected)| **selected)
+    {
+        match ranges.last_mut() {
+            Some(range) if range.end == i => range.end += 1,
+            _ => ranges.push(i..i + 1),
+        }
+    }
+    ranges
+}
+
+fn extract_chars(line: &str, char_pos: &[PositionRange], complement: bool) -> Vec<String> {
+    let chars: Vec<char> = line.chars().collect();
+    select(char_pos, chars.len(), complement)
+        .into_iter()
+        .map(|range| chars[range].iter().collect())
+        .collect()
+}
+
+/// Extract the bytes of a line, or with `no_split` only the characters whose
+/// last byte is selected: each range starts at the first byte of the
+/// character it starts in and ends with the last character it ends
+fn extract_bytes<'a>(
+    line: &'a [u8],
+    byte_pos: &[PositionRange],
+    complement: bool,
+    no_split: bool,
+) -> Vec<&'a [u8]> {
+    let boundaries = match no_split {
+        true => char_boundaries(line),
+        false => vec![],
+    };
+    let floor = |i: usize| boundaries[boundaries.partition_point(|&b| b <= i) - 1];
+    select(byte_pos, line.len(), complement)
+        .into_iter()
+        .map(|range| match no_split {
+            true => floor(range.start)..floor(range.end),
+            false => range,
+        })
+        .filter(|range| !range.is_empty())
+        .map(|range| &line[range])
+        .collect()
+}
+
+/// The offsets where the characters of a line start, and its length, where
+/// each invalid byte is a character of its own
+fn char_boundaries(line: &[u8]) -> Vec<usize> {
+    let mut boundaries = Vec::with_capacity(line.len() + 1);
+    let mut start = 0;
+    for chunk in line.utf8_chunks() {
+        let valid = chunk.valid();
+        boundaries.extend(valid.char_indices().map(|(i, _)| start + i));
+        start += valid.len();
+        boundaries.extend(start..start + chunk.invalid().len());
+        start += chunk.invalid().len();
+    }
+    boundaries.push(line.len());
+    boundaries
+}
+
+fn extract_fields<'a>(
+    fields: &[&'a str],
+    field_pos: &[PositionRange],
+    complement: bool,
+) -> Vec<&'a str> {
+    select(field_pos, fields.len(), complement)
+        .into_iter()
+        .flat_map(|range| &fields[range])
+        .copied()
+        .collect()
+}
+
 #[cfg(test)]
+#[allow(clippy::single_range_in_vec_init)]
 mod unit_tests {
-    use crate::{extract_bytes, extract_chars, parse_pos};
+    use crate::{
+        extract_bytes, extract_chars, extract_fields, parse_pos, select, Delimiter, PositionRange,
+    };
+    use regex::Regex;
+    use std::ops::Range;
+
+    fn pos(ranges: &[Range<usize>]) -> Vec<PositionRange> {
+        ranges.iter().cloned().map(PositionRange::from).collect()
+    }
+
     #[test]
     fn test_parse_pos() {
         // The empty string is an error
-        assert!(parse_pos("").is_err());
+        assert!(parse_pos("", None).is_err());
         // Zero is an error
-        let res = parse_pos("0");
+        let res = parse_pos("0", None);
         assert!(res.is_err());
         assert_eq!(res.unwrap_err().to_string(), "illegal list value: \"0\"",);
-        let res = parse_pos("0-1");
+        let res = parse_pos("0-1", None);
         assert!(res.is_err());
         assert_eq!(res.unwrap_err().to_string(), "illegal list value: \"0\"",);
         // A leading "+" is an error
-        let res = parse_pos("+1");
+        let res = parse_pos("+1", None);
         assert!(res.is_err());
         assert_eq!(res.unwrap_err().to_string(), "illegal list value: \"+1\"",);
-        let res = parse_pos("+1-2");
+        let res = parse_pos("+1-2", None);
         assert!(res.is_err());
         assert_eq!(res.unwrap_err().to_string(), "illegal list value: \"+1-2\"",);
-        let res = parse_pos("1-+2");
+        let res = parse_pos("1-+2", None);
         assert!(res.is_err());
         assert_eq!(res.unwrap_err().to_string(), "illegal list value: \"1-+2\"",);
         // Any non-number is an error
-        let res = parse_pos("a");
+        let res = parse_pos("a", None);
         assert!(res.is_err());
         assert_eq!(res.unwrap_err().to_string(), "illegal list value: \"a\"",);
-        let res = parse_pos("1,a");
+        let res = parse_pos("1,a", None);
         assert!(res.is_err());
         assert_eq!(res.unwrap_err().to_string(), "illegal list value: \"a\"",);
-        let res = parse_pos("1-a");
+        let res = parse_pos("1-a", None);
         assert!(res.is_err());
         assert_eq!(res.unwrap_err().to_string(), "illegal list value: \"1-a\"",);
-        let res = parse_pos("a-1");
+        let res = parse_pos("a-1", None);
         assert!(res.is_err());
         assert_eq!(res.unwrap_err().to_string(), "illegal list value: \"a-1\"",);
         // Wonky ranges
-        let res = parse_pos("-");
-        assert!(res.is_err());
-        let res = parse_pos(",");
+        let res = parse_pos("-", None);
         assert!(res.is_err());
-        let res = parse_pos("1,");
+        let res = parse_pos(",", None);
         assert!(res.is_err());
-        let res = parse_pos("1-");
+        let res = parse_pos("1,", None);
         assert!(res.is_err());
-        let res = parse_pos("1-1-1");
+        let res = parse_pos("1-1-1", None);
         assert!(res.is_err());
-        let res = parse_pos("1-1-a");
+        let res = parse_pos("1-1-a", None);
         assert!(res.is_err());
         // First number must be less than second
-        let res = parse_pos("1-1");
+        let res = parse_pos("1-1", None);
         assert!(res.is_err());
         assert_eq!(
             res.unwrap_err().to_string(),
             "First number in range (1) must be lower than second number (1)"
         );
-        let res = parse_pos("2-1");
+        let res = parse_pos("2-1", None);
         assert!(res.is_err());
         assert_eq!(
             res.unwrap_err().to_string(),
             "First number in range (2) must be lower than second number (1)"
         );
         // All the following are acceptable
-        let res = parse_pos("1");
+        let res = parse_pos("1", None);
+        assert!(res.is_ok());
+        assert_eq!(res.unwrap(), pos(&[0..1]));
+        let res = parse_pos("01", None);
+        assert!(res.is_ok());
+        assert_eq!(res.unwrap(), pos(&[0..1]));
+        let res = parse_pos("1,3", None);
+        assert!(res.is_ok());
+        assert_eq!(res.unwrap(), pos(&[0..1, 2..3]));
+        let res = parse_pos("001,0003", None);
         assert!(res.is_ok());
-        assert_eq!(res.unwrap(), vec![0..1]);
-        let res = parse_pos("01");
+        assert_eq!(res.unwrap(), pos(&[0..1, 2..3]));
+        let res = parse_pos("1-3", None);
         assert!(res.is_ok());
-        assert_eq!(res.unwrap(), vec![0..1]);
-        let res = parse_pos("1,3");
+        assert_eq!(res.unwrap(), pos(&[0..3]));
+        let res = parse_pos("0001-03", None);
         assert!(res.is_ok());
-        assert_eq!(res.unwrap(), vec![0..1, 2..3]);
-        let res = parse_pos("001,0003");
+        assert_eq!(res.unwrap(), pos(&[0..3]));
+        let res = parse_pos("1,7,3-5", None);
         assert!(res.is_ok());
-        assert_eq!(res.unwrap(), vec![0..1, 2..3]);
-        let res = parse_pos("1-3");
+        assert_eq!(res.unwrap(), pos(&[0..1, 6..7, 2..5]));
+        let res = parse_pos("15,19-20", None);
         assert!(res.is_ok());
-        assert_eq!(res.unwrap(), vec![0..3]);
-        let res = parse_pos("0001-03");
+        assert_eq!(res.unwrap(), pos(&[14..15, 18..20]));
+        // Open-ended ranges run from the start or to the end of the line
+        let res = parse_pos("1-", None);
         assert!(res.is_ok());
-        assert_eq!(res.unwrap(), vec![0..3]);
-        let res = parse_pos("1,7,3-5");
+        assert_eq!(res.unwrap(), vec![PositionRange::from(0..)]);
+        let res = parse_pos("-3", None);
         assert!(res.is_ok());
-        assert_eq!(res.unwrap(), vec![0..1, 6..7, 2..5]);
-        let res = parse_pos("15,19-20");
+        assert_eq!(res.unwrap(), pos(&[0..3]));
+        let res = parse_pos("-2,4-", None);
         assert!(res.is_ok());
-        assert_eq!(res.unwrap(), vec![14..15, 18..20]);
+        assert_eq!(res.unwrap(), vec![(0..2).into(), PositionRange::from(3..)]);
+        let res = parse_pos("-0", None);
+        assert!(res.is_err());
+        assert_eq!(res.unwrap_err().to_string(), "illegal list value: \"0\"",);
+        // With a header, names select the columns they name
+        let header = ["name", "email", "3"];
+        let res = parse_pos("email,name,3-", Some(&header));
+        assert!(res.is_ok());
+        assert_eq!(
+            res.unwrap(),
+            vec![(1..2).into(), (0..1).into(), (2..).into()]
+        );
+        // Names win over positions
+        let res = parse_pos("3", Some(&header));
+        assert!(res.is_ok());
+        assert_eq!(res.unwrap(), pos(&[2..3]));
+        let res = parse_pos("name,phone", Some(&header));
+        assert!(res.is_err());
+        assert_eq!(
+            res.unwrap_err().to_string(),
+            "unknown field name: \"phone\""
+        );
+        let res = parse_pos("name,0", Some(&header));
+        assert!(res.is_err());
+        assert_eq!(res.unwrap_err().to_string(), "illegal list value: \"0\"");
     }
 
     #[test]
-    fn test_extract_chars() {
-        assert_eq!(extract_chars("", &[0..1]), "".to_string());
-        assert_eq!(extract_chars("ábc", &[0..1]), "á".to_string());
-        assert_eq!(extract_chars("ábc", &[0..1, 2..3]), "ác".to_string());
-        assert_eq!(extract_chars("ábc", &[0..3]), "ábc".to_string());
-        assert_eq!(extract_chars("ábc", &[2..3, 1..2]), "cb".to_string());
-        assert_eq!(extract_chars("ábc", &[0..1, 1..2, 4..5]), "áb".to_string());
+    fn test_split() {
+        let delim = Delimiter::Str("::".to_string());
+        assert_eq!(delim.split("a::b:c::"), ["a", "b:c", ""]);
+        assert_eq!(delim.split("abc"), ["abc"]);
+        let delim = Delimiter::Regex(Regex::new(r"\s*,\s*").unwrap());
+        assert_eq!(delim.split("a , b,c"), ["a", "b", "c"]);
+        let delim = Delimiter::Whitespace;
+        assert_eq!(delim.split("  a \t b  "), ["a", "b"]);
+        assert_eq!(delim.split("   "), Vec::<&str>::new());
     }
 
     #[test]
-    fn test_extract_bytes() {
-        assert_eq!(extract_bytes("ábc", &[0..1]), "�".to_string());
-        assert_eq!(extract_bytes("ábc", &[0..2]), "á".to_string());
-        assert_eq!(extract_bytes("ábc", &[0..3]), "áb".to_string());
-        assert_eq!(extract_bytes("ábc", &[0..4]), "ábc".to_string());
-        assert_eq!(extract_bytes("ábc", &[3..4, 2..3]), "cb".to_string())
+    fn test_select() {
+        assert_eq!(select(&pos(&[0..1, 4..6]), 5, false), vec![0..1, 4..5]);
+        assert_eq!(select(&pos(&[2..3, 0..1]), 5, false), vec![2..3, 0..1]);
+        assert_eq!(select(&pos(&[6..7]), 5, false), Vec::<Range<usize>>::new());
+        assert_eq!(select(&pos(&[0..1, 3..4]), 5, true), vec![1..3, 4..5]);
+        assert_eq!(select(&pos(&[1..2, 0..3]), 5, true), vec![3..5]);
+        assert_eq!(select(&pos(&[0..5]), 5, true), Vec::<Range<usize>>::new());
+        let open = [PositionRange::from(3..), (0..1).into()];
+        assert_eq!(select(&open, 5, false), vec![3..5, 0..1]);
+        assert_eq!(select(&open, 2, false), vec![0..1]);
+        assert_eq!(select(&open, 5, true), vec![1..3]);
     }
-}
 
-pub fn run(config: Config) -> MyResult<()> {
-    for filename in &config.files {
-        match open(filename) {
-            Err(err) => eprintln!("{}: {}", filename, err),
-            Ok(file) => match &config.extract {
-                Bytes(pos) => {
-                    for line in file.lines() {
-                        println!("{}", extract_bytes(&line?, &pos));
-                    }
-                }
-                Chars(pos) => {
-                    for line in file.lines() {
-                        println!("{}", extract_chars(&line?, &pos));
-                    }
-                }
-                Fields(pos) => {
-                    let mut rdr = ReaderBuilder::new()
-                        .delimiter(config.delimiter)
-                        .has_headers(false)
-                        .from_reader(file);
-                    let mut wtr = WriterBuilder::new()
-                        .delimiter(config.delimiter)
-                        .from_writer(io::stdout());
-                    for record in rdr.records() {
-                        wtr.write_record(extract_fields(&record?, pos))?;
-                    }
-                }
-            },
-        }
+    #[test]
+    fn test_extract_chars() {
+        assert_eq!(
+            extract_chars("", &pos(&[0..1]), false),
+            Vec::<String>::new()
+        );
+        assert_eq!(extract_chars("ábc", &pos(&[0..1]), false), ["á"]);
+        assert_eq!(extract_chars("ábc", &pos(&[0..1, 2..3]), false), ["á", "c"]);
+        assert_eq!(extract_chars("ábc", &pos(&[0..3]), false), ["ábc"]);
+        assert_eq!(extract_chars("ábc", &pos(&[2..3, 1..2]), false), ["c", "b"]);
+        assert_eq!(
+            extract_chars("ábc", &pos(&[0..1, 1..2, 4..5]), false),
+            ["á", "b"]
+        );
+        assert_eq!(extract_chars("ábcd", &pos(&[1..3]), true), ["á", "d"]);
+        let open = [PositionRange::from(1..)];
+        assert_eq!(extract_chars("ábcd", &open, false), ["bcd"]);
     }
-    Ok(())
-}
 
-fn open(filename: &str) -> MyResult<Box<dyn BufRead>> {
-    match filename {
-        "-" => Ok(Box::new(BufReader::new(io::stdin()))),
-        _ => Ok(Box::new(BufReader::new(File::open(filename)?))),
+    #[test]
+    fn test_extract_bytes() {
+        let line = "ábc".as_bytes();
+        assert_eq!(extract_bytes(line, &pos(&[0..1]), false, false), [b"\xc3"]);
+        assert_eq!(
+            extract_bytes(line, &pos(&[0..2]), false, false),
+            ["á".as_bytes()]
+        );
+        assert_eq!(
+            extract_bytes(line, &pos(&[0..3]), false, false),
+            ["áb".as_bytes()]
+        );
+        assert_eq!(
+            extract_bytes(line, &pos(&[0..4]), false, false),
+            ["ábc".as_bytes()]
+        );
+        assert_eq!(
+            extract_bytes(line, &pos(&[3..4, 2..3]), false, false),
+            [b"c", b"b"]
+        );
+        assert_eq!(extract_bytes(line, &pos(&[0..2]), true, false), [b"bc"]);
+        // Characters are only selected by their last byte
+        assert_eq!(
+            extract_bytes(line, &pos(&[0..1]), false, true),
+            Vec::<&[u8]>::new()
+        );
+        assert_eq!(
+            extract_bytes(line, &pos(&[1..2]), false, true),
+            ["á".as_bytes()]
+        );
+        assert_eq!(
+            extract_bytes(line, &pos(&[1..3]), false, true),
+            ["áb".as_bytes()]
+        );
+        assert_eq!(
+            extract_bytes(line, &pos(&[0..1]), true, true),
+            ["ábc".as_bytes()]
+        );
+        // Invalid bytes are characters of their own
+        let line = b"a\xffb";
+        assert_eq!(extract_bytes(line, &pos(&[1..2]), false, true), [b"\xff"]);
+        // Even stray continuation bytes
+        let line = b"\x80abc";
+        assert_eq!(extract_bytes(line, &pos(&[0..1]), false, true), [b"\x80"]);
+        let line = b"a\x80\x80b";
+        assert_eq!(extract_bytes(line, &pos(&[2..3]), false, true), [b"\x80"]);
+        // And the bytes of a truncated character
+        let line = b"a\xe2\x82b";
+        assert_eq!(extract_bytes(line, &pos(&[1..2]), false, true), [b"\xe2"]);
     }
-}
-
-fn extract_chars(line: &str, char_pos: &[Range<usize>]) -> String {
-    let chars: Vec<char> = line.chars().collect();
-    char_pos
-        .iter()
-        .filter_map(|range| chars.get(range.clone()))
-        .flatten()
-        .collect()
-}
 
-fn extract_bytes(line: &str, byte_pos: &[Range<usize>]) -> String {
-    let bytes: Vec<u8> = line.as_bytes().to_vec();
-    byte_pos
-        .iter()
-        .filter_map(|range| bytes.get(range.clone()))
-        .map(String::from_utf8_lossy)
-        .collect()
-}
-
-fn extract_fields(record: &StringRecord, field_pos: &[Range<usize>]) -> Vec<String> {
-    let fields: Vec<&str> = record.iter().collect();
-    field_pos
-        .iter()
-        .filter_map(|range| fields.get(range.clone()))
-        .flatten()
-        .map(|s| s.to_string())
-        .collect()
+    #[test]
+    fn test_extract_fields() {
+        let rec = ["Captain", "Sham", "12345"];
+        assert_eq!(extract_fields(&rec, &pos(&[0..1]), false), &["Captain"]);
+        assert_eq!(extract_fields(&rec, &pos(&[1..2]), false), &["Sham"]);
+        assert_eq!(
+            extract_fields(&rec, &pos(&[0..1, 2..3]), false),
+            &["Captain", "12345"]
+        );
+        assert_eq!(
+            extract_fields(&rec, &pos(&[0..1, 3..4]), false),
+            &["Captain"]
+        );
+        assert_eq!(
+            extract_fields(&rec, &pos(&[1..2, 0..1]), false),
+            &["Sham", "Captain"]
+        );
+        assert_eq!(
+            extract_fields(&rec, &pos(&[1..2]), true),
+            &["Captain", "12345"]
+        );
+    }
 }
@@ -0,0 +1,170 @@
+use crate::{open, MyResult};
+use clap::Parser;
+use std::{
+    cell::RefCell,
+    error::Error,
+    io::{self, BufRead, Write},
+    rc::Rc,
+};
+
+#[derive(Debug, Parser)]
+#[command(name = "paster", author, version, about = "Rust paste", long_about = None)]
+pub struct Cli {
+    #[arg(help = "Input file(s)", value_name = "FILE", default_value = "-")]
+    files: Vec<String>,
+
+    #[arg(
+        short,
+        long,
+        help = "Delimiters used in turn, where \\n, \\t, \\\\ and \\0 (none) are escapes",
+        value_name = "LIST",
+        default_value = "\t"
+    )]
+    delimiters: String,
+
+    #[arg(short, long, help = "Paste the lines of each file in turn")]
+    serial: bool,
+}
+
+#[derive(Debug)]
+pub struct Config {
+    files: Vec<String>,
+    delimiters: Vec<String>,
+    serial: bool,
+}
+
+pub fn get_args() -> MyResult<Config> {
+    let cli = Cli::try_parse().map_err(|e| -> Box<dyn Error> { e.into() })?;
+    Ok(Config {
+        files: cli.files,
+        delimiters: parse_delim_list(&cli.delimiters)?,
+        serial: cli.serial,
+    })
+}
+
+/// Parse a list of delimiters, one per character or escape
+///
+/// Unlike cut's `-d`, which takes one delimiter as it is, paste's `-d` is a
+/// list with the escapes of POSIX paste, so it cannot use `parse_byte` or
+/// cutr's literal `--delim`.
+fn parse_delim_list(list: &str) -> Result<Vec<String>, String> {
+    if list.is_empty() {
+        return Ok(vec![String::new()]);
+    }
+    let mut delimiters = vec![];
+    let mut chars = list.chars();
+    while let Some(c) = chars.next() {
+        let delimiter = match c {
+            '\\' => match chars.next() {
+                Some('n') => "\n".to_string(),
+                Some('t') => "\t".to_string(),
+                Some('0') => String::new(),
+                Some(c) => c.to_string(),
+                None => {
+                    return Err(format!(
+                        "delimiter list ends with an unescaped backslash: {}",
+                        list
+                    ))
+                }
+            },
+            c => c.to_string(),
+        };
+        delimiters.push(delimiter);
+    }
+    Ok(delimiters)
+}
+
+pub fn run(config: Config) -> MyResult<()> {
+    // Every "-" reads from the same stdin, taking its lines in turn
+    let stdin: Rc<RefCell<Box<dyn BufRead>>> = Rc::new(RefCell::new(open("-")?));
+    let mut files = vec![];
+    for filename in &config.files {
+        match filename.as_str() {
+            "-" => files.push(Rc::clone(&stdin)),
+            _ => match open(filename) {
+                Err(err) => eprintln!("{}: {}", filename, err),
+                Ok(file) => files.push(Rc::new(RefCell::new(file))),
+            },
+        }
+    }
+    let mut stdout = io::stdout().lock();
+    if config.serial {
+        for file in &files {
+            let mut file = file.borrow_mut();
+            let mut out = vec![];
+            let mut line = vec![];
+            let mut delimiters = config.delimiters.iter().cycle();
+            let mut first = true;
+            while read_line(&mut *file, &mut line)? {
+                if !first {
+                    out.extend(delimiters.next().map(String::as_bytes).unwrap_or_default());
+                }
+                first = false;
+                out.append(&mut line);
+            }
+            out.push(b'\n');
+            stdout.write_all(&out)?;
+        }
+    } else {
+        let mut line = vec![];
+        loop {
+            let mut out = vec![];
+            let mut any = false;
+            let mut delimiters = config.delimiters.iter().cycle();
+            for (i, file) in files.iter().enumerate() {
+                if i > 0 {
+                    out.extend(delimiters.next().map(String::as_bytes).unwrap_or_default());
+                }
+                if read_line(&mut *file.borrow_mut(), &mut line)? {
+                    any = true;
+                    out.append(&mut line);
+                }
+            }
+            if !any {
+                break;
+            }
+            out.push(b'\n');
+            stdout.write_all(&out)?;
+        }
+    }
+    Ok(())
+}
+
+/// Read the next line of `file` into `line` without its newline, returning
+/// whether there was one
+fn read_line(file: &mut dyn BufRead, line: &mut Vec<u8>) -> io::Result<bool> {
+    line.clear();
+    if file.read_until(b'\n', line)? == 0 {
+        return Ok(false);
+    }
+    if line.last() == Some(&b'\n') {
+        line.pop();
+    }
+    Ok(true)
+}
+
+#[cfg(test)]
+mod unit_tests {
+    use super::parse_delim_list;
+
+    #[test]
+    fn test_parse_delim_list() {
+        assert_eq!(parse_delim_list("\t"), Ok(vec!["\t".to_string()]));
+        assert_eq!(parse_delim_list(",;"), Ok(vec![",".into(), ";".into()]));
+        assert_eq!(
+            parse_delim_list(r"\n\t\\\0→"),
+            Ok(vec![
+                "\n".into(),
+                "\t".into(),
+                "\\".into(),
+                "".into(),
+                "→".into()
+            ])
+        );
+        assert_eq!(parse_delim_list(""), Ok(vec!["".to_string()]));
+        assert_eq!(
+            parse_delim_list(r",\"),
+            Err(r"delimiter list ends with an unescaped backslash: ,\".to_string())
+        );
+    }
+}
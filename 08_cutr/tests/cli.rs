@@ -1,3 +1,6 @@
+// The baseline tests are kept as they were written, before this lint
+#![allow(clippy::needless_borrows_for_generic_args)]
+
 use assert_cmd::Command;
 use predicates::prelude::*;
 use rand::{distributions::Alphanumeric, Rng};
@@ -96,7 +99,7 @@ fn dies_bad_digit_chars() -> TestResult {
 fn dies_empty_delimiter() -> TestResult {
     dies(
         &[CSV, "-f", "1", "-d", ""],
-        "--delim \"\" must be a single byte",
+        "--delim \"\" must not be empty",
     )
 }
 
@@ -104,7 +107,7 @@ fn dies_empty_delimiter() -> TestResult {
 #[test]
 fn dies_bad_delimiter() -> TestResult {
     dies(
-        &[CSV, "-f", "1", "-d", ",,"],
+        &[CSV, "-f", "1", "--csv", "-d", ",,"],
         "--delim \",,\" must be a single byte",
     )
 }
@@ -161,6 +164,17 @@ fn run(args: &[&str], expected_file: &str) -> TestResult {
     Ok(())
 }
 
+// --------------------------------------------------
+fn run_stdin(args: &[&str], input: &str, expected: &str) -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(args)
+        .write_stdin(input)
+        .assert()
+        .success()
+        .stdout(expected.to_string());
+    Ok(())
+}
+
 // --------------------------------------------------
 fn run_lossy(args: &[&str], expected_file: &str) -> TestResult {
     let contents = fs::read(expected_file)?;
@@ -340,3 +354,367 @@ fn tsv_c1_8() -> TestResult {
 fn repeated_value() -> TestResult {
     run(&[BOOKS, "-c", "1,1"], "tests/expected/books.c1,1.out")
 }
+
+// --------------------------------------------------
+#[test]
+fn tsv_f2_complement() -> TestResult {
+    run(
+        &[TSV, "-f", "2", "--complement"],
+        "tests/expected/movies1.tsv.f2.complement.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn tsv_f1_3_output_delimiter() -> TestResult {
+    run(
+        &[TSV, "-f", "1,3", "--output-delimiter", ","],
+        "tests/expected/movies1.tsv.f1,3.odcomma.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn csv_f1_3_output_delimiter() -> TestResult {
+    run(
+        &[CSV, "-d", ",", "-f", "1,3", "--output-delimiter", "\t"],
+        "tests/expected/movies1.csv.f1,3.dcomma.odtab.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn tsv_c1_3_complement() -> TestResult {
+    run(
+        &[TSV, "-c", "1-3", "--complement"],
+        "tests/expected/movies1.tsv.c1-3.complement.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn tsv_c1_5_6_output_delimiter() -> TestResult {
+    run(
+        &[TSV, "-c", "1,5-6", "--output-delimiter", ":"],
+        "tests/expected/movies1.tsv.c1,5-6.odcolon.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn tsv_b1_2_complement() -> TestResult {
+    run(
+        &[TSV, "-b", "1-2", "--complement"],
+        "tests/expected/movies1.tsv.b1-2.complement.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn tsv_b1_3_complement_output_delimiter() -> TestResult {
+    run(
+        &[TSV, "-b", "1,3", "--complement", "--output-delimiter", "_"],
+        "tests/expected/movies1.tsv.b1,3.complement.odunderscore.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_bad_output_delimiter() -> TestResult {
+    dies(
+        &[TSV, "-f", "1", "--csv", "--output-delimiter", ",,"],
+        "--output-delimiter \",,\" must be a single byte",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn tsv_f2_open() -> TestResult {
+    run(&[TSV, "-f", "2-"], "tests/expected/movies1.tsv.f2-.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn csv_f_open_2() -> TestResult {
+    run(
+        &[CSV, "-f", "-2", "-d", ","],
+        "tests/expected/movies1.csv.f-2.dcomma.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn tsv_c_open_3() -> TestResult {
+    run(&[TSV, "-c", "-3"], "tests/expected/movies1.tsv.c-3.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn tsv_b5_open() -> TestResult {
+    run(&[TSV, "-b", "5-"], "tests/expected/movies1.tsv.b5-.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn fields_ignore_quotes() -> TestResult {
+    let input = "\"a,b\",c\n";
+    run_stdin(&["-d", ",", "-f", "1"], input, "\"a\n")?;
+    run_stdin(&["-d", ",", "-f", "2-"], input, "b\",c\n")?;
+    run_stdin(&["-d", ",", "-f", "1", "--csv"], input, "\"a,b\"\n")?;
+    run_stdin(
+        &["-d", ",", "-f", "1-", "--csv", "--output-delimiter", ";"],
+        input,
+        "a,b;c\n",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn fields_multibyte_output_delimiter() -> TestResult {
+    run_stdin(
+        &["-f", "1,3", "--output-delimiter", " | "],
+        "a\tb\tc\n",
+        "a | c\n",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn only_delimited() -> TestResult {
+    let input = "a\tb\nno delimiter\n\tc\n";
+    run_stdin(&["-f", "2"], input, "b\nno delimiter\nc\n")?;
+    run_stdin(&["-f", "2", "-s"], input, "b\nc\n")?;
+    run_stdin(&["-f", "2", "--only-delimited"], input, "b\nc\n")?;
+    run_stdin(
+        &["-d", ",", "-f", "1", "--csv", "-s"],
+        "a,b\n\"c,d\"\n",
+        "a\n",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_only_delimited_chars() -> TestResult {
+    dies(
+        &[TSV, "-c", "1", "-s"],
+        "'--chars <CHARS>' cannot be used with '--only-delimited'",
+    )?;
+    dies(
+        &[TSV, "-b", "1", "--csv"],
+        "'--bytes <BYTES>' cannot be used with '--csv'",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn csv_header_names() -> TestResult {
+    run_stdin(
+        &["-d", ",", "--csv", "--header", "-f", "director,1"],
+        "title,year,director\nThe Blues Brothers,1980,John Landis\n",
+        "director,title\nJohn Landis,The Blues Brothers\n",
+    )?;
+    run_stdin(
+        &["--csv", "--header", "-f", "b,3-", "--complement"],
+        "a\tb\tc\td\n1\t2\t3\t4\n",
+        "a\n1\n",
+    )?;
+    run_stdin(&["--csv", "--header", "-f", "a"], "", "")
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_csv_header_unknown_name() -> TestResult {
+    dies(
+        &[CSV, "-d", ",", "--csv", "--header", "-f", "title,email"],
+        "unknown field name: \"email\"",
+    )?;
+    dies(
+        &[CSV, "-d", ",", "--csv", "--header", "-f", "title,0"],
+        "illegal list value: \"0\"",
+    )?;
+    dies(
+        &[CSV, "-d", ",", "--header", "-f", "title"],
+        "the following required arguments were not provided",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn multibyte_delimiter() -> TestResult {
+    run_stdin(&["-d", "::", "-f", "3,1"], "a::b::c\nd\n", "c::a\nd\n")?;
+    run_stdin(
+        &["-d", "→", "-f", "2-", "--output-delimiter", ","],
+        "á→b→c\n",
+        "b,c\n",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn regex_delimiter() -> TestResult {
+    run_stdin(
+        &["--regex-delim", r"\s*[,;]\s*", "-f", "3,1"],
+        "a , b;c\nd\n",
+        "c a\nd\n",
+    )?;
+    run_stdin(
+        &[
+            "--regex-delim",
+            "-+",
+            "-f",
+            "2",
+            "-s",
+            "--output-delimiter",
+            "|",
+        ],
+        "a---b\nc\n",
+        "b\n",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn whitespace_delimiter() -> TestResult {
+    let input = "  PID TTY   CMD\n    1 ?     init\nsolo\n";
+    run_stdin(&["-w", "-f", "1,3"], input, "PID CMD\n1 init\nsolo\n")?;
+    run_stdin(
+        &["--whitespace", "-f", "2-", "-s", "--output-delimiter", "\t"],
+        input,
+        "TTY\tCMD\n?\tinit\n",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_bad_regex_delimiter() -> TestResult {
+    dies(&[TSV, "-f", "1", "--regex-delim", "("], "regex parse error")?;
+    dies(
+        &[TSV, "-f", "1", "-w", "-d", ","],
+        "'--whitespace' cannot be used with '--delim <DELIMITER>'",
+    )?;
+    dies(
+        &[TSV, "-f", "1", "--csv", "--regex-delim", ","],
+        "cannot be used with",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn bytes_no_split() -> TestResult {
+    run_stdin(&["-b", "1", "-n"], "ábc\n", "\n")?;
+    run_stdin(&["-n", "-b", "2,4"], "ábc\n", "ác\n")?;
+    run_stdin(&["-n", "-b", "2-3", "--complement"], "ábcd\n", "cd\n")?;
+    run_stdin(&["-n", "-b", "-3"], "aé→\n", "aé\n")
+}
+
+// --------------------------------------------------
+#[test]
+fn bytes_raw() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["-b", "1,2", "--raw", "--output-delimiter", ":"])
+        .write_stdin(b"\xc3\xa1\n\xffb\n".as_slice())
+        .assert()
+        .success()
+        .stdout(b"\xc3:\xa1\n\xff:b\n".as_slice());
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn invalid_utf8_input() -> TestResult {
+    let input = b"a\xffb\tc\n".as_slice();
+    Command::cargo_bin(PRG)?
+        .args(["-b", "2"])
+        .write_stdin(input)
+        .assert()
+        .success()
+        .stdout("\u{fffd}\n");
+    Command::cargo_bin(PRG)?
+        .args(["-c", "1-3"])
+        .write_stdin(input)
+        .assert()
+        .success()
+        .stdout("a\u{fffd}b\n");
+    Command::cargo_bin(PRG)?
+        .args(["-f", "2"])
+        .write_stdin(input)
+        .assert()
+        .success()
+        .stdout("c\n");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn csv_convert_dialect() -> TestResult {
+    let input = "a,\"b,c\",d\n\"x \"\"y\"\"\",2,3\n";
+    run_stdin(
+        &["-d", ",", "-f", "3,1-2", "--csv", "--output-delimiter", "|"],
+        input,
+        "d|a|b,c\n3|\"x \"\"y\"\"\"|2\n",
+    )?;
+    run_stdin(
+        &["-d", ",", "-f", "1", "--csv", "--output-escape", "\\"],
+        input,
+        "a\n\"x \\\"y\\\"\"\n",
+    )?;
+    run_stdin(
+        &[
+            "-d", ";", "-f", "2", "--csv", "--quote", "'", "--escape", "\\",
+        ],
+        "a;'b;\\'c'\n",
+        "'b;\\'c'\n",
+    )?;
+    run_stdin(
+        &[
+            "-d",
+            ";",
+            "-f",
+            "2",
+            "--csv",
+            "--quote",
+            "'",
+            "--output-quote",
+            "\"",
+        ],
+        "a;'b;c'\n",
+        "\"b;c\"\n",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn csv_quote_style() -> TestResult {
+    let input = "a,\"b,c\"\n";
+    run_stdin(
+        &["-d", ",", "-f", "1-", "--csv", "--quote-all", "--crlf"],
+        input,
+        "\"a\",\"b,c\"\r\n",
+    )?;
+    run_stdin(
+        &["-d", ",", "-f", "1-", "--csv", "--quote-minimal"],
+        input,
+        "a,\"b,c\"\n",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_bad_csv_dialect() -> TestResult {
+    dies(
+        &[CSV, "-f", "1", "--csv", "--quote", "''"],
+        "--quote \"''\" must be a single byte",
+    )?;
+    dies(
+        &[CSV, "-f", "1", "--csv", "--escape", ""],
+        "--escape \"\" must be a single byte",
+    )?;
+    dies(
+        &[CSV, "-f", "1", "--quote-all"],
+        "the following required arguments were not provided",
+    )?;
+    dies(
+        &[CSV, "-f", "1", "--csv", "--quote-all", "--quote-minimal"],
+        "'--quote-all' cannot be used with '--quote-minimal'",
+    )
+}
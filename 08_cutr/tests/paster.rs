@@ -0,0 +1,101 @@
+use assert_cmd::Command;
+use predicates::prelude::*;
+use std::fs;
+
+type TestResult = Result<(), Box<dyn std::error::Error>>;
+
+const PRG: &str = "paster";
+const CSV: &str = "tests/inputs/movies1.csv";
+const TSV: &str = "tests/inputs/movies1.tsv";
+const BOOKS: &str = "tests/inputs/books.txt";
+
+// --------------------------------------------------
+fn run(args: &[&str], expected_file: &str) -> TestResult {
+    let expected = fs::read_to_string(expected_file)?;
+    Command::cargo_bin(PRG)?
+        .args(args)
+        .assert()
+        .success()
+        .stdout(expected);
+    Ok(())
+}
+
+// --------------------------------------------------
+fn run_stdin(args: &[&str], input: &str, expected: &str) -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(args)
+        .write_stdin(input)
+        .assert()
+        .success()
+        .stdout(expected.to_string());
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn skips_bad_file() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args([CSV, "does-not-exist", BOOKS])
+        .assert()
+        .success()
+        .stderr(predicate::str::is_match(
+            "does-not-exist: .* [(]os error 2[)]",
+        )?);
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_bad_delimiter_list() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["-d", r",\", CSV])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            r"delimiter list ends with an unescaped backslash: ,\",
+        ));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn parallel() -> TestResult {
+    run(
+        &[TSV, BOOKS],
+        "tests/expected/paster.movies1.tsv.books.txt.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn parallel_delimiters() -> TestResult {
+    run(
+        &["-d", ",:", CSV, BOOKS, CSV],
+        "tests/expected/paster.dcommacolon.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn serial() -> TestResult {
+    run(&["-s", BOOKS, CSV], "tests/expected/paster.serial.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn serial_delimiters() -> TestResult {
+    run(
+        &["-s", "-d", r"\t\n", BOOKS],
+        "tests/expected/paster.books.txt.serial.dtabnl.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn stdin_repeated() -> TestResult {
+    let input = "1\n2\n3\n4\n5\n";
+    run_stdin(&[], input, input)?;
+    run_stdin(&["-", "-"], input, "1\t2\n3\t4\n5\t\n")?;
+    run_stdin(&["-d", r"\0", "-", "-", "-"], input, "123\n45\n")?;
+    run_stdin(&["-s", "-d", ",", "-", "-"], input, "1,2,3,4,5\n\n")
+}
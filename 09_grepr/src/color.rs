@@ -0,0 +1,133 @@
+use clap::ValueEnum;
+use std::{
+    env,
+    io::{self, IsTerminal, Write},
+};
+
+/// When to color the output
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum When {
+    Auto,
+    Always,
+    Never,
+}
+
+impl When {
+    /// Whether to color the output, which for `Auto` is only when stdout is
+    /// a terminal that can show colors
+    pub fn enabled(self) -> bool {
+        match self {
+            When::Always => true,
+            When::Never => false,
+            When::Auto => {
+                io::stdout().is_terminal() && env::var("TERM").map_or(true, |term| term != "dumb")
+            }
+        }
+    }
+}
+
+/// The SGR parameters of each part of the output, which are set like those
+/// of grep with a `GREP_COLORS` value such as `ms=01;31:fn=35:ne`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Colors {
+    /// Matches in selected lines
+    pub selected_match: String,
+    /// Matches in context lines
+    pub context_match: String,
+    pub filename: String,
+    pub line_number: String,
+    pub byte_offset: String,
+    /// The separators after each prefix and between groups of lines
+    pub separator: String,
+    /// Whether to erase to the end of the line after each color
+    erase: bool,
+}
+
+impl Default for Colors {
+    fn default() -> Self {
+        Colors {
+            selected_match: "01;31".to_string(),
+            context_match: "01;31".to_string(),
+            filename: "35".to_string(),
+            line_number: "32".to_string(),
+            byte_offset: "32".to_string(),
+            separator: "36".to_string(),
+            erase: true,
+        }
+    }
+}
+
+impl Colors {
+    /// The colors set by the `GREP_COLORS` environment variable
+    pub fn from_env() -> Self {
+        env::var("GREP_COLORS").map_or_else(|_| Colors::default(), |spec| Colors::parse(&spec))
+    }
+
+    /// The default colors changed by `spec`, where unknown capabilities are
+    /// ignored like grep does
+    pub fn parse(spec: &str) -> Self {
+        let mut colors = Colors::default();
+        for cap in spec.split(':') {
+            let (name, value) = cap.split_once('=').unwrap_or((cap, ""));
+            let value = value.to_string();
+            match name {
+                "mt" => {
+                    colors.selected_match = value.clone();
+                    colors.context_match = value;
+                }
+                "ms" => colors.selected_match = value,
+                "mc" => colors.context_match = value,
+                "fn" => colors.filename = value,
+                "ln" => colors.line_number = value,
+                "bn" => colors.byte_offset = value,
+                "se" => colors.separator = value,
+                "ne" => colors.erase = false,
+                _ => (),
+            }
+        }
+        colors
+    }
+
+    /// Write `text` in the color given by the SGR parameters `sgr`, or as
+    /// it is if there are none
+    pub fn paint(&self, out: &mut dyn Write, sgr: &str, text: &str) -> io::Result<()> {
+        if sgr.is_empty() {
+            return out.write_all(text.as_bytes());
+        }
+        let erase = if self.erase { "\x1b[K" } else { "" };
+        write!(out, "\x1b[{}m{}{}\x1b[m{}", sgr, erase, text, erase)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Colors;
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(Colors::parse(""), Colors::default());
+        let colors = Colors::parse("mt=04:fn=:ln=1;33:xx=7:ne");
+        assert_eq!(colors.selected_match, "04");
+        assert_eq!(colors.context_match, "04");
+        assert_eq!(colors.filename, "");
+        assert_eq!(colors.line_number, "1;33");
+        assert_eq!(colors.byte_offset, "32");
+        assert!(!colors.erase);
+        let colors = Colors::parse("mt=04:ms=35");
+        assert_eq!(colors.selected_match, "35");
+        assert_eq!(colors.context_match, "04");
+    }
+
+    #[test]
+    fn test_paint() {
+        let mut out = vec![];
+        Colors::default().paint(&mut out, "35", "file").unwrap();
+        assert_eq!(out, b"\x1b[35m\x1b[Kfile\x1b[m\x1b[K");
+        let mut out = vec![];
+        Colors::parse("ne").paint(&mut out, "35", "file").unwrap();
+        assert_eq!(out, b"\x1b[35mfile\x1b[m");
+        let mut out = vec![];
+        Colors::default().paint(&mut out, "", "file").unwrap();
+        assert_eq!(out, b"file");
+    }
+}
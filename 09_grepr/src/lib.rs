@@ -1,23 +1,66 @@
-use clap::Parser;
-use regex::{Regex, RegexBuilder};
+mod color;
+mod matcher;
+mod sink;
+
+use crate::{
+    color::{Colors, When},
+    matcher::{Matcher, Syntax},
+    sink::{Discard, Format, Line, Printer, Sink},
+};
+use clap::{error::ErrorKind, ArgAction, CommandFactory, Parser};
 use std::{
+    collections::VecDeque,
     error::Error,
     fs::{self, File},
-    io::{self, BufRead, BufReader},
+    io::{self, BufRead, BufReader, BufWriter, IsTerminal, LineWriter, Write},
+    mem,
 };
 use walkdir::WalkDir;
 
 type MyResult<T> = Result<T, Box<dyn Error>>;
 
 #[derive(Debug, Parser)]
-#[command(author, version, about, long_about = None)]
+#[command(author, version, about, long_about = None, disable_help_flag = true)]
 pub struct Cli {
-    #[arg(help = "Search pattern")]
-    pattern: Regex,
+    #[arg(
+        help = "Search pattern, unless there are -e or -f patterns",
+        required_unless_present_any = ["regexp", "pattern_files"]
+    )]
+    pattern: Option<String>,
 
-    #[arg(help = "Input file(s)", value_name = "FILE", default_value = "-")]
+    #[arg(help = "Input file(s) [default: -]", value_name = "FILE")]
     files: Vec<String>,
 
+    #[arg(
+        short = 'e',
+        long,
+        help = "Search pattern, which can be repeated",
+        value_name = "PATTERN",
+        allow_hyphen_values = true
+    )]
+    regexp: Vec<String>,
+
+    #[arg(
+        short = 'f',
+        long = "file",
+        help = "Read the search patterns from FILE, one per line",
+        value_name = "FILE"
+    )]
+    pattern_files: Vec<String>,
+
+    #[arg(
+        short = 'F',
+        long,
+        help = "Search for the patterns as fixed strings, not regexes"
+    )]
+    fixed_strings: bool,
+
+    #[arg(short = 'w', long, help = "Match only whole words")]
+    word_regexp: bool,
+
+    #[arg(short = 'x', long, help = "Match only whole lines")]
+    line_regexp: bool,
+
     #[arg(short, long, help = "Count occurrences")]
     count: bool,
 
@@ -29,43 +72,264 @@ pub struct Cli {
 
     #[arg(short, long, help = "Recursive search")]
     recursive: bool,
+
+    #[arg(short = 'n', long, help = "Print the line number of each line")]
+    line_number: bool,
+
+    #[arg(short, long, help = "Print the byte offset of each line")]
+    byte_offset: bool,
+
+    #[arg(
+        short = 'H',
+        long,
+        help = "Print the file name of each line",
+        overrides_with = "no_filename"
+    )]
+    with_filename: bool,
+
+    #[arg(
+        short = 'h',
+        long,
+        help = "Never print file names",
+        overrides_with = "with_filename"
+    )]
+    no_filename: bool,
+
+    #[arg(
+        long,
+        help = "File name to print for stdin",
+        value_name = "LABEL",
+        default_value = "(standard input)"
+    )]
+    label: String,
+
+    #[arg(
+        short = 'A',
+        long,
+        help = "Print NUM lines after each selected line",
+        value_name = "NUM"
+    )]
+    after_context: Option<usize>,
+
+    #[arg(
+        short = 'B',
+        long,
+        help = "Print NUM lines before each selected line",
+        value_name = "NUM"
+    )]
+    before_context: Option<usize>,
+
+    #[arg(
+        short = 'C',
+        long,
+        help = "Print NUM lines around each selected line",
+        value_name = "NUM"
+    )]
+    context: Option<usize>,
+
+    #[arg(
+        long,
+        help = "Line printed between groups of context lines",
+        value_name = "SEP",
+        default_value = "--",
+        overrides_with = "no_group_separator"
+    )]
+    group_separator: String,
+
+    #[arg(
+        long,
+        help = "Print nothing between groups of context lines",
+        overrides_with = "group_separator"
+    )]
+    no_group_separator: bool,
+
+    #[arg(short, long, help = "Print only the matches, one per line")]
+    only_matching: bool,
+
+    #[arg(
+        long,
+        visible_alias = "colour",
+        help = "Highlight the matches, with colors set by GREP_COLORS",
+        value_name = "WHEN",
+        value_enum,
+        num_args = 0..=1,
+        require_equals = true,
+        default_value = "never",
+        default_missing_value = "auto"
+    )]
+    color: When,
+
+    #[arg(
+        short = 'l',
+        long,
+        help = "Print only the names of the files with selected lines"
+    )]
+    files_with_matches: bool,
+
+    #[arg(
+        short = 'L',
+        long,
+        help = "Print only the names of the files without selected lines",
+        conflicts_with = "files_with_matches"
+    )]
+    files_without_match: bool,
+
+    #[arg(
+        short,
+        long,
+        visible_alias = "silent",
+        help = "Print nothing, only exit with 0 as soon as a line is selected"
+    )]
+    quiet: bool,
+
+    #[arg(
+        short,
+        long,
+        help = "Stop reading a file after NUM selected lines",
+        value_name = "NUM"
+    )]
+    max_count: Option<usize>,
+
+    #[arg(long, action = ArgAction::Help, help = "Print help")]
+    help: Option<bool>,
+
+    #[arg(skip)]
+    matcher: Matcher,
 }
 
 pub fn get_args() -> MyResult<Cli> {
-    let mut cli = Cli::try_parse()?;
-    cli.pattern = RegexBuilder::new(cli.pattern.as_str())
-        .case_insensitive(cli.insensitive)
-        .build()?;
+    // Exit with 2 on usage errors like grep
+    let mut cli = Cli::parse();
+    // With -e or -f, the first argument is a file like the others
+    let mut patterns = cli.regexp.clone();
+    if let Some(pattern) = cli.pattern.take() {
+        match patterns.is_empty() && cli.pattern_files.is_empty() {
+            true => patterns.push(pattern),
+            false => cli.files.insert(0, pattern),
+        }
+    }
+    if cli.files.is_empty() {
+        cli.files.push("-".to_string());
+    }
+    // A pattern with newlines is one pattern per line, like in a file
+    let mut patterns: Vec<String> = patterns
+        .iter()
+        .flat_map(|pattern| pattern.split('\n'))
+        .map(str::to_string)
+        .collect();
+    for filename in &cli.pattern_files {
+        let file = open(filename).map_err(|e| format!("{}: {}", filename, e))?;
+        for line in file.lines() {
+            patterns.push(line.map_err(|e| format!("{}: {}", filename, e))?);
+        }
+    }
+    let syntax = Syntax {
+        fixed_strings: cli.fixed_strings,
+        ignore_case: cli.insensitive,
+        word: cli.word_regexp,
+        line: cli.line_regexp,
+    };
+    cli.matcher = Matcher::new(&patterns, syntax)
+        .unwrap_or_else(|e| Cli::command().error(ErrorKind::ValueValidation, e).exit());
     Ok(cli)
 }
 
-pub fn run(cli: Cli) -> MyResult<()> {
+/// Search the files, returning the exit status: 0 if a line was selected,
+/// 1 if none was, or 2 if some file could not be read, unless a line was
+/// selected with `--quiet`
+pub fn run(cli: Cli) -> MyResult<i32> {
     let entries = find_files(&cli.files, cli.recursive);
-    let n_entrie = entries.len();
+    let with_filename = !cli.no_filename && (cli.with_filename || entries.len() > 1);
+    let colors = cli.color.enabled().then(Colors::from_env);
+    let format = Format {
+        line_number: cli.line_number,
+        byte_offset: cli.byte_offset,
+        only_matching: cli.only_matching,
+        colors: colors.as_ref(),
+    };
+    let context = Context {
+        before: cli.before_context.or(cli.context).unwrap_or(0),
+        after: cli.after_context.or(cli.context).unwrap_or(0),
+    };
+    // Like grep, only flush each line when someone may be watching
+    let stdout = io::stdout();
+    let mut out: Box<dyn Write> = if stdout.is_terminal() {
+        Box::new(LineWriter::new(stdout.lock()))
+    } else {
+        Box::new(BufWriter::new(stdout.lock()))
+    };
+    let group_separator = (!cli.no_group_separator).then_some(cli.group_separator.as_str());
+    let mut printer = Printer::new(&mut out, &cli.matcher, format, group_separator);
+    // Listing a file or exiting only takes one selected line
+    let list = cli.files_with_matches || cli.files_without_match;
+    let max_count = match list || cli.quiet {
+        true => Some(cli.max_count.map_or(1, |max_count| max_count.min(1))),
+        false => cli.max_count,
+    };
+    let (mut selected, mut failed) = (false, false);
     for entry in entries {
         match entry {
-            Err(e) => eprintln!("{}", e),
+            Err(e) => {
+                eprintln!("{}", e);
+                failed = true;
+            }
             Ok(filename) => match open(&filename) {
-                Err(e) => eprintln!("{}: {}", filename, e),
+                Err(e) => {
+                    eprintln!("{}: {}", filename, e);
+                    failed = true;
+                }
                 Ok(file) => {
-                    let matches = find_lines(file, &cli.pattern, cli.invert_match)?;
-                    let mut prefix = String::new();
-                    if n_entrie > 1 {
-                        prefix.push_str(&format!("{filename}:"))
-                    }
-                    if cli.count {
-                        println!("{}{}", prefix, matches.len());
-                        continue;
-                    }
-                    for m in matches {
-                        print!("{}{}", prefix, m);
+                    let filename = match filename.as_str() {
+                        "-" => cli.label.as_str(),
+                        filename => filename,
+                    };
+                    let (matcher, invert_match) = (&cli.matcher, cli.invert_match);
+                    let count = if list || cli.quiet || cli.count {
+                        let context = Context::default();
+                        search(
+                            file,
+                            matcher,
+                            invert_match,
+                            context,
+                            max_count,
+                            &mut Discard,
+                        )?
+                    } else {
+                        printer.set_filename(with_filename.then_some(filename));
+                        search(
+                            file,
+                            matcher,
+                            invert_match,
+                            context,
+                            max_count,
+                            &mut printer,
+                        )?
+                    };
+                    selected |= count > 0;
+                    if cli.quiet {
+                        if selected {
+                            break;
+                        }
+                    } else if list {
+                        if cli.files_with_matches == (count > 0) {
+                            printer.list(filename)?;
+                        }
+                    } else if cli.count {
+                        printer.set_filename(with_filename.then_some(filename));
+                        printer.count(count)?;
                     }
                 }
             },
         }
     }
-
-    Ok(())
+    drop(printer);
+    out.flush()?;
+    Ok(match (selected, failed) {
+        (true, _) if cli.quiet => 0,
+        (_, true) => 2,
+        (true, false) => 0,
+        (false, false) => 1,
+    })
 }
 
 fn find_files(paths: &[String], recursive: bool) -> Vec<MyResult<String>> {
@@ -99,13 +363,126 @@ fn find_files(paths: &[String], recursive: bool) -> Vec<MyResult<String>> {
     res
 }
 
+/// How many lines to hand over before and after each selected line
+#[derive(Debug, Clone, Copy, Default)]
+struct Context {
+    before: usize,
+    after: usize,
+}
+
+/// Search `file` line by line, handing the selected lines and the lines of
+/// context around them to `sink` as they are found, and returning how many
+/// lines were selected
+///
+/// After `max_count` selected lines, only the context after the last one is
+/// read.
+fn search<T: BufRead>(
+    mut file: T,
+    matcher: &Matcher,
+    invert_match: bool,
+    context: Context,
+    max_count: Option<usize>,
+    sink: &mut dyn Sink,
+) -> MyResult<usize> {
+    // The last lines not handed over, as many as the context before
+    let mut before: VecDeque<(usize, u64, String)> = VecDeque::new();
+    let mut after = 0;
+    let mut count = 0;
+    let mut last = None;
+    let mut text = String::new();
+    let (mut number, mut offset) = (1, 0);
+    // Groups only exist when there is some context
+    let mut gap = |number: usize, sink: &mut dyn Sink| -> io::Result<()> {
+        let follows = last.is_some_and(|last| last + 1 == number);
+        last = Some(number);
+        match context.before > 0 || context.after > 0 {
+            true if !follows => sink.gap(),
+            _ => Ok(()),
+        }
+    };
+    loop {
+        let full = max_count.is_some_and(|max_count| count >= max_count);
+        if full && after == 0 {
+            return Ok(count);
+        }
+        let len = file.read_line(&mut text)?;
+        if len == 0 {
+            return Ok(count);
+        }
+        if text.ends_with('\n') {
+            text.pop();
+        }
+        if !full && invert_match ^ matcher.is_match(&text) {
+            count += 1;
+            for (number, offset, text) in before.drain(..) {
+                gap(number, sink)?;
+                sink.context(&Line {
+                    number,
+                    offset,
+                    text: &text,
+                })?;
+            }
+            gap(number, sink)?;
+            sink.matched(&Line {
+                number,
+                offset,
+                text: &text,
+            })?;
+            after = context.after;
+        } else if after > 0 {
+            gap(number, sink)?;
+            sink.context(&Line {
+                number,
+                offset,
+                text: &text,
+            })?;
+            after -= 1;
+        } else if context.before > 0 {
+            // Reuse the oldest line's buffer once there are enough of them
+            let mut spare = match before.len() == context.before {
+                true => before
+                    .pop_front()
+                    .map(|(_, _, text)| text)
+                    .unwrap_or_default(),
+                false => String::new(),
+            };
+            mem::swap(&mut text, &mut spare);
+            before.push_back((number, offset, spare));
+        }
+        number += 1;
+        offset += len as u64;
+        text.clear();
+    }
+}
+
+fn open(filename: &str) -> MyResult<Box<dyn BufRead>> {
+    match filename {
+        "-" => Ok(Box::new(BufReader::new(io::stdin()))),
+        _ => Ok(Box::new(BufReader::new(File::open(filename)?))),
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use crate::{find_files, find_lines};
+    use crate::{
+        color::Colors,
+        find_files,
+        matcher::{Matcher, Syntax},
+        search,
+        sink::{Discard, Format, Printer},
+        Context,
+    };
     use rand::{distributions::Alphanumeric, Rng};
-    use regex::{Regex, RegexBuilder};
     use std::io::Cursor;
 
+    fn matcher(pattern: &str, ignore_case: bool) -> Matcher {
+        let syntax = Syntax {
+            ignore_case,
+            ..Syntax::default()
+        };
+        Matcher::new(&[pattern.to_string()], syntax).unwrap()
+    }
+
     #[test]
     fn test_find_files() {
         // Verify that the function finds a file known to exist
@@ -148,52 +525,207 @@ mod tests {
     }
 
     #[test]
-    fn test_find_lines() {
+    fn test_search() {
         let text = b"Lorem\nIpsum\r\nDOLOR";
         // The pattern _or_ should match the one line, "Lorem"
-        let re1 = Regex::new("or").unwrap();
-        let matches = find_lines(Cursor::new(&text), &re1, false);
-        assert!(matches.is_ok());
-        assert_eq!(matches.unwrap().len(), 1);
+        let re1 = matcher("or", false);
+        let res = search(
+            Cursor::new(&text),
+            &re1,
+            false,
+            Context::default(),
+            None,
+            &mut Discard,
+        );
+        assert!(res.is_ok());
+        assert_eq!(res.unwrap(), 1);
         // When inverted, the function should match the other two lines
-        let matches = find_lines(Cursor::new(&text), &re1, true);
-        assert!(matches.is_ok());
-        assert_eq!(matches.unwrap().len(), 2);
+        let res = search(
+            Cursor::new(&text),
+            &re1,
+            true,
+            Context::default(),
+            None,
+            &mut Discard,
+        );
+        assert!(res.is_ok());
+        assert_eq!(res.unwrap(), 2);
         // This regex will be case-insensitive
-        let re2 = RegexBuilder::new("or")
-            .case_insensitive(true)
-            .build()
-            .unwrap();
+        let re2 = matcher("or", true);
         // The two lines "Lorem" and "DOLOR" should match
-        let matches = find_lines(Cursor::new(&text), &re2, false);
-        assert!(matches.is_ok());
-        assert_eq!(matches.unwrap().len(), 2);
+        let res = search(
+            Cursor::new(&text),
+            &re2,
+            false,
+            Context::default(),
+            None,
+            &mut Discard,
+        );
+        assert!(res.is_ok());
+        assert_eq!(res.unwrap(), 2);
         // When inverted, the one remaining line should match
-        let matches = find_lines(Cursor::new(&text), &re2, true);
-        assert!(matches.is_ok());
-        assert_eq!(matches.unwrap().len(), 1);
+        let res = search(
+            Cursor::new(&text),
+            &re2,
+            true,
+            Context::default(),
+            None,
+            &mut Discard,
+        );
+        assert!(res.is_ok());
+        assert_eq!(res.unwrap(), 1);
     }
-}
 
-fn find_lines<T: BufRead>(
-    mut file: T,
-    pattern: &Regex,
-    invert_match: bool,
-) -> MyResult<Vec<String>> {
-    let mut matches = Vec::new();
-    let mut line = String::new();
-    while file.read_line(&mut line)? > 0 {
-        if invert_match ^ pattern.is_match(&line) {
-            matches.push(line.clone());
-        }
-        line.clear();
+    #[test]
+    fn test_search_printer() {
+        let text = b"Lorem\nIpsum\r\nDOLOR";
+        let re = matcher("o", false);
+        // The selected lines are written as they are, always ending a line
+        let mut out = vec![];
+        let mut printer = Printer::new(&mut out, &re, Format::default(), None);
+        let res = search(
+            Cursor::new(&text),
+            &re,
+            true,
+            Context::default(),
+            None,
+            &mut printer,
+        );
+        assert!(res.is_ok());
+        assert_eq!(out, b"Ipsum\r\nDOLOR\n");
+        // After the name of their file if there is one
+        let mut out = vec![];
+        let mut printer = Printer::new(&mut out, &re, Format::default(), None);
+        printer.set_filename(Some("text"));
+        let res = search(
+            Cursor::new(&text),
+            &re,
+            false,
+            Context::default(),
+            None,
+            &mut printer,
+        );
+        assert!(res.is_ok());
+        assert_eq!(out, b"text:Lorem\n");
+        // Then its number and offset if they are wanted
+        let mut out = vec![];
+        let format = Format {
+            line_number: true,
+            byte_offset: true,
+            ..Format::default()
+        };
+        let mut printer = Printer::new(&mut out, &re, format, None);
+        printer.set_filename(Some("text"));
+        let res = search(
+            Cursor::new(&text),
+            &re,
+            true,
+            Context::default(),
+            None,
+            &mut printer,
+        );
+        assert!(res.is_ok());
+        assert_eq!(out, b"text:2:6:Ipsum\r\ntext:3:13:DOLOR\n");
     }
-    Ok(matches)
-}
 
-fn open(filename: &str) -> MyResult<Box<dyn BufRead>> {
-    match filename {
-        "-" => Ok(Box::new(BufReader::new(io::stdin()))),
-        _ => Ok(Box::new(BufReader::new(File::open(filename)?))),
+    #[test]
+    fn test_search_context() {
+        let text = b"1 a\n2\n3\n4 a\n5\n6\n7\n8 a\n9\n";
+        let re = matcher("a", false);
+        let format = Format {
+            line_number: true,
+            ..Format::default()
+        };
+        let search_context = |before, after| {
+            let mut out = vec![];
+            let mut printer = Printer::new(&mut out, &re, format, Some("--"));
+            let context = Context { before, after };
+            search(Cursor::new(&text), &re, false, context, None, &mut printer).unwrap();
+            String::from_utf8(out).unwrap()
+        };
+        // Context lines use "-" where the selected lines use ":"
+        assert_eq!(
+            search_context(0, 1),
+            "1:1 a\n2-2\n--\n4:4 a\n5-5\n--\n8:8 a\n9-9\n"
+        );
+        // Windows that overlap or touch are merged
+        assert_eq!(
+            search_context(2, 0),
+            "1:1 a\n2-2\n3-3\n4:4 a\n--\n6-6\n7-7\n8:8 a\n"
+        );
+        assert_eq!(
+            search_context(1, 2),
+            "1:1 a\n2-2\n3-3\n4:4 a\n5-5\n6-6\n7-7\n8:8 a\n9-9\n"
+        );
+    }
+
+    #[test]
+    fn test_search_max_count() {
+        let text = b"1 a\n2\n3 a\n4 a\n5\n6 a\n";
+        let re = matcher("a", false);
+        let search_max = |max_count, after| {
+            let mut out = vec![];
+            let mut printer = Printer::new(&mut out, &re, Format::default(), Some("--"));
+            let context = Context { before: 0, after };
+            let count = search(
+                Cursor::new(&text),
+                &re,
+                false,
+                context,
+                max_count,
+                &mut printer,
+            );
+            (count.unwrap(), String::from_utf8(out).unwrap())
+        };
+        assert_eq!(search_max(Some(0), 2), (0, "".to_string()));
+        assert_eq!(search_max(Some(2), 0), (2, "1 a\n3 a\n".to_string()));
+        // The lines after the last one are context, even if they match
+        assert_eq!(
+            search_max(Some(2), 2),
+            (2, "1 a\n2\n3 a\n4 a\n5\n".to_string())
+        );
+        assert_eq!(search_max(None, 0), (4, "1 a\n3 a\n4 a\n6 a\n".to_string()));
+    }
+
+    #[test]
+    fn test_search_matches() {
+        let text = b"a1b22\nccc\n333";
+        let re = matcher("[0-9]+", false);
+        // Each match goes on a line of its own, at its own offset
+        let mut out = vec![];
+        let format = Format {
+            byte_offset: true,
+            only_matching: true,
+            ..Format::default()
+        };
+        let mut printer = Printer::new(&mut out, &re, format, None);
+        let res = search(
+            Cursor::new(&text),
+            &re,
+            false,
+            Context::default(),
+            None,
+            &mut printer,
+        );
+        assert!(res.is_ok());
+        assert_eq!(out, b"1:1\n3:22\n10:333\n");
+        // Or is highlighted in its line
+        let mut out = vec![];
+        let colors = Colors::parse("ms=1:ne");
+        let format = Format {
+            colors: Some(&colors),
+            ..Format::default()
+        };
+        let mut printer = Printer::new(&mut out, &re, format, None);
+        let res = search(
+            Cursor::new(&text),
+            &re,
+            false,
+            Context::default(),
+            None,
+            &mut printer,
+        );
+        assert!(res.is_ok());
+        assert_eq!(out, b"a\x1b[1m1\x1b[mb\x1b[1m22\x1b[m\n\x1b[1m333\x1b[m\n");
     }
 }
@@ -0,0 +1,205 @@
+use crate::MyResult;
+use aho_corasick::{AhoCorasick, Input, MatchKind};
+use regex::{Regex, RegexBuilder};
+use std::{cmp::Reverse, iter, ops::Range};
+
+/// How the patterns are read and what their matches must be
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Syntax {
+    /// The patterns are strings to find as they are, not regexes
+    pub fixed_strings: bool,
+    pub ignore_case: bool,
+    /// Matches must not have a word character just before or after them
+    pub word: bool,
+    /// Matches must be whole lines, which wins over `word`
+    pub line: bool,
+}
+
+/// Finds the matches of any of a list of patterns in a line, where no
+/// patterns match nothing and an empty one matches everything
+#[derive(Debug)]
+pub enum Matcher {
+    /// The patterns as one regex, with the word and line checks built in
+    Regex(Regex),
+    /// Fixed strings all looked for at once, with the word and line checks
+    /// done on each match
+    Literals {
+        searcher: AhoCorasick,
+        syntax: Syntax,
+    },
+}
+
+impl Default for Matcher {
+    fn default() -> Self {
+        let patterns: [&str; 0] = [];
+        Matcher::Literals {
+            searcher: AhoCorasick::new(patterns).expect("no patterns"),
+            syntax: Syntax::default(),
+        }
+    }
+}
+
+impl Matcher {
+    pub fn new(patterns: &[String], syntax: Syntax) -> MyResult<Self> {
+        // Aho-Corasick only folds the case of ASCII letters
+        let ascii = patterns.iter().all(|pattern| pattern.is_ascii());
+        if patterns.is_empty() || (syntax.fixed_strings && (ascii || !syntax.ignore_case)) {
+            // Word matches have to be picked among all the overlapping ones
+            let match_kind = match syntax.word && !syntax.line {
+                true => MatchKind::Standard,
+                false => MatchKind::LeftmostLongest,
+            };
+            let searcher = AhoCorasick::builder()
+                .ascii_case_insensitive(syntax.ignore_case)
+                .match_kind(match_kind)
+                .build(patterns)?;
+            return Ok(Matcher::Literals { searcher, syntax });
+        }
+        let mut alternatives = Vec::with_capacity(patterns.len());
+        for pattern in patterns {
+            let pattern = match syntax.fixed_strings {
+                true => regex::escape(pattern),
+                false => {
+                    // Check each pattern on its own, so that one cannot
+                    // close the group around another
+                    if let Err(e) = Regex::new(pattern) {
+                        return Err(format!("invalid value '{}': {}", pattern, e).into());
+                    }
+                    pattern.to_string()
+                }
+            };
+            alternatives.push(if syntax.line {
+                format!("^(?:{})$", pattern)
+            } else if syntax.word {
+                format!(r"\b{{start-half}}(?:{})\b{{end-half}}", pattern)
+            } else {
+                format!("(?:{})", pattern)
+            });
+        }
+        let regex = RegexBuilder::new(&alternatives.join("|"))
+            .case_insensitive(syntax.ignore_case)
+            .build()?;
+        Ok(Matcher::Regex(regex))
+    }
+
+    pub fn is_match(&self, text: &str) -> bool {
+        match self {
+            Matcher::Regex(regex) => regex.is_match(text),
+            Matcher::Literals { .. } => self.find_at(text, 0).is_some(),
+        }
+    }
+
+    /// The matches in `text` that do not overlap, from left to right
+    pub fn find_iter<'a>(&'a self, text: &'a str) -> impl Iterator<Item = Range<usize>> + 'a {
+        let mut start = Some(0);
+        iter::from_fn(move || {
+            let found = self.find_at(text, start?)?;
+            // Step over the next character after an empty match
+            start = match found.is_empty() {
+                true => text[found.end..]
+                    .chars()
+                    .next()
+                    .map(|c| found.end + c.len_utf8()),
+                false => Some(found.end),
+            };
+            Some(found)
+        })
+    }
+
+    /// The leftmost match in `text` from `start` on, where the text before
+    /// `start` still counts for the word and line checks
+    fn find_at(&self, text: &str, start: usize) -> Option<Range<usize>> {
+        let (searcher, syntax) = match self {
+            Matcher::Regex(regex) => return regex.find_at(text, start).map(|m| m.range()),
+            Matcher::Literals { searcher, syntax } => (searcher, syntax),
+        };
+        let input = Input::new(text).range(start..);
+        if syntax.line {
+            // The longest match at the start is the only one that can be
+            // the whole line
+            searcher
+                .find(input)
+                .map(|m| m.range())
+                .filter(|found| found.start == 0 && found.end == text.len())
+        } else if syntax.word {
+            searcher
+                .find_overlapping_iter(input)
+                .map(|m| m.range())
+                .filter(|found| is_word(text, found))
+                .min_by_key(|found| (found.start, Reverse(found.end)))
+        } else {
+            searcher.find(input).map(|m| m.range())
+        }
+    }
+}
+
+/// Whether `found` has no word character just before or after it in `text`
+fn is_word(text: &str, found: &Range<usize>) -> bool {
+    let is_word_char = |c: char| c.is_alphanumeric() || c == '_';
+    !text[..found.start]
+        .chars()
+        .next_back()
+        .is_some_and(is_word_char)
+        && !text[found.end..].chars().next().is_some_and(is_word_char)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Matcher, Syntax};
+
+    fn matches(patterns: &[&str], syntax: Syntax, text: &str) -> Vec<String> {
+        let patterns: Vec<String> = patterns.iter().map(|p| p.to_string()).collect();
+        let matcher = Matcher::new(&patterns, syntax).unwrap();
+        matcher
+            .find_iter(text)
+            .map(|found| text[found].to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_find_iter() {
+        let regex = Syntax::default();
+        let fixed = Syntax {
+            fixed_strings: true,
+            ..Syntax::default()
+        };
+        assert_eq!(matches(&["o+", "a"], regex, "foo bar"), ["oo", "a"]);
+        assert_eq!(matches(&["o+"], fixed, "foo o+"), ["o+"]);
+        // Fixed strings are matched leftmost longest like grep does
+        assert_eq!(matches(&["o", "oo"], fixed, "foo"), ["oo"]);
+        assert_eq!(matches(&[""], fixed, "ab"), ["", "", ""]);
+        assert!(matches(&[], regex, "ab").is_empty());
+        // Only ASCII letters are folded by Aho-Corasick, so others need a regex
+        let ignore_case = Syntax {
+            ignore_case: true,
+            ..fixed
+        };
+        assert_eq!(matches(&["ab"], ignore_case, "aB Ab"), ["aB", "Ab"]);
+        assert_eq!(matches(&["é"], ignore_case, "éÉ"), ["é", "É"]);
+    }
+
+    #[test]
+    fn test_find_iter_word_line() {
+        for fixed_strings in [false, true] {
+            let word = Syntax {
+                fixed_strings,
+                word: true,
+                ..Syntax::default()
+            };
+            assert_eq!(matches(&["foo"], word, "foobar foo_ foo"), ["foo"]);
+            assert_eq!(matches(&["@foo"], word, "a@foo @foo"), ["@foo"]);
+            // A longer match is tried when the shorter one is not a word
+            assert_eq!(matches(&["foo", "foo_bar"], word, "foo_bar"), ["foo_bar"]);
+            assert_eq!(matches(&[""], word, " a "), ["", ""]);
+            let line = Syntax {
+                fixed_strings,
+                line: true,
+                word: true,
+                ..Syntax::default()
+            };
+            assert_eq!(matches(&["foo", "foo bar"], line, "foo bar"), ["foo bar"]);
+            assert!(matches(&["foo"], line, "foo bar").is_empty());
+            assert_eq!(matches(&[""], line, ""), [""]);
+        }
+    }
+}
@@ -0,0 +1,193 @@
+use crate::{color::Colors, matcher::Matcher};
+use std::io::{self, Write};
+
+/// A line of a file, with where it is in the file
+#[derive(Debug, Clone, Copy)]
+pub struct Line<'a> {
+    /// The number of the line, counted from 1
+    pub number: usize,
+    /// The offset in bytes of the start of the line
+    pub offset: u64,
+    /// The text of the line, without its newline
+    pub text: &'a str,
+}
+
+/// Where the lines selected by a search go, as soon as they are found
+pub trait Sink {
+    /// Take a selected line
+    fn matched(&mut self, line: &Line) -> io::Result<()>;
+
+    /// Take a line around a selected one
+    fn context(&mut self, _line: &Line) -> io::Result<()> {
+        Ok(())
+    }
+
+    /// Mark the start of a group of lines that does not follow the lines
+    /// taken before it
+    fn gap(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// How the lines are printed
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Format<'a> {
+    pub line_number: bool,
+    pub byte_offset: bool,
+    /// Print only the matches in the selected lines, one per line
+    pub only_matching: bool,
+    pub colors: Option<&'a Colors>,
+}
+
+/// Writes the selected lines as `file:number:offset:text`, leaving out what
+/// is not wanted, and the context lines with `-` instead of `:`
+pub struct Printer<'a> {
+    out: &'a mut dyn Write,
+    filename: Option<String>,
+    /// The patterns whose matches are printed or highlighted
+    matcher: &'a Matcher,
+    format: Format<'a>,
+    /// The line written between groups of lines, if any
+    group_separator: Option<&'a str>,
+    /// Whether any line was written, in this file or an earlier one
+    written: bool,
+}
+
+impl<'a> Printer<'a> {
+    pub fn new(
+        out: &'a mut dyn Write,
+        matcher: &'a Matcher,
+        format: Format<'a>,
+        group_separator: Option<&'a str>,
+    ) -> Self {
+        Printer {
+            out,
+            filename: None,
+            matcher,
+            format,
+            group_separator,
+            written: false,
+        }
+    }
+
+    /// Print `filename` before the lines of the next file, if it is set
+    pub fn set_filename(&mut self, filename: Option<&str>) {
+        self.filename = filename.map(str::to_string);
+    }
+
+    /// Write the name of a file on a line of its own
+    pub fn list(&mut self, filename: &str) -> io::Result<()> {
+        self.paint(|colors| &colors.filename, filename)?;
+        self.out.write_all(b"\n")
+    }
+
+    /// Write the number of lines selected in the current file
+    pub fn count(&mut self, count: usize) -> io::Result<()> {
+        let colors = self.format.colors;
+        if let Some(filename) = &self.filename {
+            paint(self.out, colors, |colors| &colors.filename, filename)?;
+            paint(self.out, colors, |colors| &colors.separator, ":")?;
+        }
+        writeln!(self.out, "{}", count)
+    }
+
+    fn paint(&mut self, color: Color, text: &str) -> io::Result<()> {
+        paint(self.out, self.format.colors, color, text)
+    }
+
+    fn write_prefix(&mut self, number: usize, offset: u64, separator: &str) -> io::Result<()> {
+        let colors = self.format.colors;
+        if let Some(filename) = &self.filename {
+            paint(self.out, colors, |colors| &colors.filename, filename)?;
+            paint(self.out, colors, |colors| &colors.separator, separator)?;
+        }
+        if self.format.line_number {
+            self.paint(|colors| &colors.line_number, &number.to_string())?;
+            self.paint(|colors| &colors.separator, separator)?;
+        }
+        if self.format.byte_offset {
+            self.paint(|colors| &colors.byte_offset, &offset.to_string())?;
+            self.paint(|colors| &colors.separator, separator)?;
+        }
+        Ok(())
+    }
+
+    fn write(&mut self, line: &Line, separator: &str, selected: bool) -> io::Result<()> {
+        self.written = true;
+        let color: Color = match selected {
+            true => |colors| &colors.selected_match,
+            false => |colors| &colors.context_match,
+        };
+        if self.format.only_matching {
+            for found in self.matcher.find_iter(line.text) {
+                if found.is_empty() {
+                    continue;
+                }
+                self.write_prefix(line.number, line.offset + found.start as u64, separator)?;
+                self.paint(color, &line.text[found])?;
+                self.out.write_all(b"\n")?;
+            }
+            return Ok(());
+        }
+        self.write_prefix(line.number, line.offset, separator)?;
+        let mut end = 0;
+        if self.format.colors.is_some() {
+            for found in self.matcher.find_iter(line.text) {
+                if found.is_empty() {
+                    continue;
+                }
+                self.out
+                    .write_all(&line.text.as_bytes()[end..found.start])?;
+                end = found.end;
+                self.paint(color, &line.text[found])?;
+            }
+        }
+        self.out.write_all(&line.text.as_bytes()[end..])?;
+        self.out.write_all(b"\n")
+    }
+}
+
+impl Sink for Printer<'_> {
+    fn matched(&mut self, line: &Line) -> io::Result<()> {
+        self.write(line, ":", true)
+    }
+
+    fn context(&mut self, line: &Line) -> io::Result<()> {
+        // Like grep, only the selected lines are printed with -o
+        match self.format.only_matching {
+            true => Ok(()),
+            false => self.write(line, "-", false),
+        }
+    }
+
+    fn gap(&mut self) -> io::Result<()> {
+        match self.group_separator {
+            Some(separator) if self.written && !self.format.only_matching => {
+                self.paint(|colors| &colors.separator, separator)?;
+                self.out.write_all(b"\n")
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Picks the color of a part of the output
+type Color = fn(&Colors) -> &String;
+
+/// Write `text` in the color picked by `color`, if there are colors
+fn paint(out: &mut dyn Write, colors: Option<&Colors>, color: Color, text: &str) -> io::Result<()> {
+    match colors {
+        Some(colors) => colors.paint(out, color(colors), text),
+        None => out.write_all(text.as_bytes()),
+    }
+}
+
+/// Drops the lines, for when only their number matters
+#[derive(Debug)]
+pub struct Discard;
+
+impl Sink for Discard {
+    fn matched(&mut self, _line: &Line) -> io::Result<()> {
+        Ok(())
+    }
+}
@@ -1,3 +1,6 @@
+// The baseline tests are kept as they were written, before this lint
+#![allow(clippy::needless_borrows_for_generic_args)]
+
 use assert_cmd::Command;
 use predicates::prelude::*;
 use rand::{distributions::Alphanumeric, Rng};
@@ -273,3 +276,356 @@ fn stdin_insensitive_count() -> TestResult {
         .stdout(expected);
     Ok(())
 }
+
+// --------------------------------------------------
+#[test]
+fn line_number() -> TestResult {
+    run(
+        &["-n", "the", BUSTLE],
+        "tests/expected/bustle.txt.the.lowercase.n",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn line_number_byte_offset_multiple() -> TestResult {
+    run(
+        &["-nbi", "the", BUSTLE, FOX],
+        "tests/expected/bustle.fox.the.insensitive.nb",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn with_filename() -> TestResult {
+    run(
+        &["-H", "--line-number", "dog", FOX],
+        "tests/expected/fox.txt.dog.Hn",
+    )?;
+    run(
+        &["--with-filename", "-c", "the", BUSTLE],
+        "tests/expected/bustle.txt.the.lowercase.count.H",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn no_filename() -> TestResult {
+    run(
+        &["-h", "-n", "-i", "the", BUSTLE, FOX],
+        "tests/expected/bustle.fox.the.insensitive.hn",
+    )?;
+    // The last of -h and -H wins
+    run(
+        &["-H", "--no-filename", "-n", "-i", "the", BUSTLE, FOX],
+        "tests/expected/bustle.fox.the.insensitive.hn",
+    )?;
+    run(
+        &["-h", "-H", "-n", "dog", FOX],
+        "tests/expected/fox.txt.dog.Hn",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn stdin_label() -> TestResult {
+    let input = fs::read_to_string(FOX)?;
+    let expected = "(standard input):1:The quick brown fox jumps over the lazy dog.\n";
+    Command::cargo_bin(PRG)?
+        .args(["-Hn", "fox"])
+        .write_stdin(input.clone())
+        .assert()
+        .stdout(expected);
+    Command::cargo_bin(PRG)?
+        .args(["-n", "--label", "fox", "fox", "-", EMPTY])
+        .write_stdin(input)
+        .assert()
+        .stdout("fox:1:The quick brown fox jumps over the lazy dog.\n");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn after_context() -> TestResult {
+    run(
+        &["-A1", "the", BUSTLE],
+        "tests/expected/bustle.txt.the.lowercase.A1",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn before_context() -> TestResult {
+    run(
+        &["--before-context", "2", "-n", "the", BUSTLE],
+        "tests/expected/bustle.txt.the.lowercase.B2.n",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn context_multiple() -> TestResult {
+    run(
+        &["-C1", "-n", "-i", "the", BUSTLE, FOX, NOBODY],
+        "tests/expected/all.the.insensitive.C1.n",
+    )?;
+    // -A and -B win over -C
+    run(
+        &["-A1", "-B1", "-C5", "-n", "-i", "the", BUSTLE, FOX, NOBODY],
+        "tests/expected/all.the.insensitive.C1.n",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn group_separator() -> TestResult {
+    run(
+        &["-C1", "--group-separator=XX", "-i", "the", BUSTLE, NOBODY],
+        "tests/expected/bustle.nobody.the.insensitive.C1.sepXX",
+    )?;
+    run(
+        &["-C1", "--no-group-separator", "-i", "the", BUSTLE, NOBODY],
+        "tests/expected/bustle.nobody.the.insensitive.C1.nosep",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn only_matching() -> TestResult {
+    run(
+        &["-o", "-b", "-i", "the", BUSTLE],
+        "tests/expected/bustle.txt.the.insensitive.ob",
+    )?;
+    run(
+        &["--only-matching", "-n", "-i", "-H", "t.e", BUSTLE, FOX],
+        "tests/expected/bustle.fox.t.e.insensitive.onH",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn color() -> TestResult {
+    run(
+        &["--color=always", "-n", "-i", "the", BUSTLE, FOX],
+        "tests/expected/bustle.fox.the.insensitive.n.color",
+    )?;
+    // The matches in context lines are highlighted too
+    run(
+        &["--colour=always", "-C1", "-v", "-n", "the", BUSTLE],
+        "tests/expected/bustle.txt.the.lowercase.v.C1.n.color",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn color_auto() -> TestResult {
+    // The output of the tests is not a terminal
+    run(
+        &["--color", "the", BUSTLE],
+        "tests/expected/bustle.txt.the.lowercase",
+    )?;
+    run(
+        &["--color=never", "the", BUSTLE],
+        "tests/expected/bustle.txt.the.lowercase",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn grep_colors() -> TestResult {
+    let expected = fs::read_to_string("tests/expected/bustle.txt.the.lowercase.H.grep_colors")?;
+    Command::cargo_bin(PRG)?
+        .args(["--color=always", "-H", "the", BUSTLE])
+        .env("GREP_COLORS", "mt=04:fn=1;34:ne")
+        .assert()
+        .stdout(expected);
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn files_with_matches() -> TestResult {
+    run(
+        &["-l", "-i", "the", BUSTLE, EMPTY, FOX, NOBODY],
+        "tests/expected/all.the.insensitive.l",
+    )?;
+    // Only the names are printed, even with -c or -n
+    run(
+        &["-l", "-c", "-n", "-i", "the", BUSTLE, EMPTY, FOX, NOBODY],
+        "tests/expected/all.the.insensitive.l",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn files_without_match() -> TestResult {
+    run(
+        &["-L", "-i", "the", BUSTLE, EMPTY, FOX, NOBODY],
+        "tests/expected/all.the.insensitive.L",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn max_count() -> TestResult {
+    run(
+        &["-m2", "-n", "the", BUSTLE],
+        "tests/expected/bustle.txt.the.lowercase.m2.n",
+    )?;
+    run(
+        &["-c", "-m", "2", "-i", "the", BUSTLE, NOBODY],
+        "tests/expected/bustle.nobody.the.insensitive.m2.count",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn max_count_context() -> TestResult {
+    // The lines after the last one are printed as context
+    run(
+        &["-m1", "-A2", "-n", "The", BUSTLE],
+        "tests/expected/bustle.txt.the.capitalized.m1.A2.n",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn quiet() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["-q", "the", BUSTLE])
+        .assert()
+        .code(0)
+        .stdout("");
+    Command::cargo_bin(PRG)?
+        .args(["--silent", "zzz", BUSTLE])
+        .assert()
+        .code(1)
+        .stdout("");
+    // A selected line hides the errors from the exit status
+    Command::cargo_bin(PRG)?
+        .args(["-q", "the", &gen_bad_file(), BUSTLE])
+        .assert()
+        .code(0);
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn exit_status() -> TestResult {
+    Command::cargo_bin(PRG)?.args(["fox", FOX]).assert().code(0);
+    Command::cargo_bin(PRG)?.args(["zzz", FOX]).assert().code(1);
+    Command::cargo_bin(PRG)?
+        .args(["-m0", "fox", FOX])
+        .assert()
+        .code(1)
+        .stdout("");
+    // -L lists the files but still fails when no line was selected
+    Command::cargo_bin(PRG)?
+        .args(["-L", "zzz", FOX])
+        .assert()
+        .code(1)
+        .stdout(format!("{}\n", FOX));
+    Command::cargo_bin(PRG)?
+        .args(["fox", &gen_bad_file(), FOX])
+        .assert()
+        .code(2);
+    Command::cargo_bin(PRG)?
+        .args(["-m", "x", "fox", FOX])
+        .assert()
+        .code(2);
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn regexp_multiple() -> TestResult {
+    // The first argument is a file once there is a -e pattern
+    run(
+        &["-e", "the", BUSTLE, "--regexp", "Who", NOBODY],
+        "tests/expected/bustle.nobody.the.who.e",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn pattern_file() -> TestResult {
+    let expected = fs::read_to_string("tests/expected/bustle.nobody.the.who.insensitive.v.f")?;
+    Command::cargo_bin(PRG)?
+        .args(["-i", "-v", "-f", "-", BUSTLE, NOBODY])
+        .write_stdin("the\nWho\n")
+        .assert()
+        .success()
+        .stdout(expected);
+    // No patterns select no lines
+    Command::cargo_bin(PRG)?
+        .args(["-f", "-", FOX])
+        .write_stdin("")
+        .assert()
+        .code(1);
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_bad_pattern_file() -> TestResult {
+    let bad = gen_bad_file();
+    let expected = format!("{}: .* [(]os error 2[)]", bad);
+    Command::cargo_bin(PRG)?
+        .args(["-f", &bad, FOX])
+        .assert()
+        .code(2)
+        .stderr(predicate::str::is_match(expected)?);
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn fixed_strings() -> TestResult {
+    run(
+        &["-o", "-F", "-i", "-e", "you", "-e", "Nobody", NOBODY],
+        "tests/expected/nobody.txt.you.nobody.insensitive.o.F",
+    )?;
+    // Fixed strings are not regexes
+    Command::cargo_bin(PRG)?
+        .args(["-F", "*foo", FOX])
+        .assert()
+        .code(1);
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn word_regexp() -> TestResult {
+    run(
+        &["-w", "-i", "-n", "the", BUSTLE, NOBODY],
+        "tests/expected/bustle.nobody.the.insensitive.w.n",
+    )?;
+    run(
+        &["-ow", "-e", "up", "-e", "us", BUSTLE, NOBODY],
+        "tests/expected/bustle.nobody.up.us.o.w",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn line_regexp() -> TestResult {
+    run(
+        &["-v", "-x", "-n", "", BUSTLE],
+        "tests/expected/bustle.txt.empty.v.x.n",
+    )?;
+    run(
+        &["-ixF", "until eternity.", BUSTLE],
+        "tests/expected/bustle.txt.until.insensitive.x.F",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn huge_context() -> TestResult {
+    // Room for the lines before is not made up front
+    run(
+        &["-B", "18446744073709551615", "-n", "the", BUSTLE],
+        "tests/expected/bustle.txt.the.lowercase.B_huge.n",
+    )?;
+    run(&["-C", "1000000000000", "the", BUSTLE], BUSTLE)
+}
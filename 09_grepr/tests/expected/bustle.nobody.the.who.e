@@ -0,0 +1,5 @@
+tests/inputs/bustle.txt:The sweeping up the heart,
+tests/inputs/nobody.txt:I'm Nobody! Who are you?
+tests/inputs/nobody.txt:Then there's a pair of us!
+tests/inputs/nobody.txt:Don't tell! they'd advertise—you know!
+tests/inputs/nobody.txt:To tell one's name—the livelong June—
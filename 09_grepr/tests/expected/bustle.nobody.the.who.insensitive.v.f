@@ -0,0 +1,11 @@
+tests/inputs/bustle.txt:Is solemnest of industries
+tests/inputs/bustle.txt:Enacted upon earth,—
+tests/inputs/bustle.txt:
+tests/inputs/bustle.txt:And putting love away
+tests/inputs/bustle.txt:We shall not want to use again
+tests/inputs/bustle.txt:Until eternity.
+tests/inputs/nobody.txt:Are you—Nobody—too?
+tests/inputs/nobody.txt:
+tests/inputs/nobody.txt:How dreary—to be—Somebody!
+tests/inputs/nobody.txt:How public—like a Frog—
+tests/inputs/nobody.txt:To an admiring Bog!
@@ -0,0 +1,2 @@
+tests/inputs/bustle.txt:up
+tests/inputs/nobody.txt:us